@@ -22,7 +22,10 @@ pub fn get_ones_and_threes(sorted_numbers: &[i32]) -> (i32, i32) {
     (ones, threes)
 }
 
-pub fn solve_part_two(sorted_numbers: &[i32]) -> i64 {
+fn ways_to_device(sorted_numbers: &[i32]) -> Vec<i64> {
+    if sorted_numbers.is_empty() {
+        return vec![];
+    }
     let mut num_of_ways = vec![0; sorted_numbers.len()];
     num_of_ways[sorted_numbers.len() - 1] = 1;
     for i in (0..sorted_numbers.len() - 1).rev() {
@@ -32,6 +35,26 @@ pub fn solve_part_two(sorted_numbers: &[i32]) -> i64 {
             j += 1;
         }
     }
+    num_of_ways
+}
+
+fn ways_from_outlet(sorted_numbers: &[i32]) -> Vec<i64> {
+    let mut num_of_ways = vec![0; sorted_numbers.len()];
+    for i in 0..sorted_numbers.len() {
+        if sorted_numbers[i] <= 3 {
+            num_of_ways[i] = 1;
+        }
+        let mut j = i;
+        while j > 0 && sorted_numbers[i] - sorted_numbers[j - 1] <= 3 {
+            num_of_ways[i] += num_of_ways[j - 1];
+            j -= 1;
+        }
+    }
+    num_of_ways
+}
+
+pub fn solve_part_two(sorted_numbers: &[i32]) -> i64 {
+    let num_of_ways = ways_to_device(sorted_numbers);
     (0..3)
         .map(|i| {
             sorted_numbers
@@ -42,6 +65,122 @@ pub fn solve_part_two(sorted_numbers: &[i32]) -> i64 {
         .sum()
 }
 
+// Fewest / most adapters in a chain from the outlet to the adapter at each index, or None when
+// it can't be reached. `better` picks between the current best and a new candidate.
+fn chain_lengths(sorted_numbers: &[i32], better: fn(usize, usize) -> usize) -> Vec<Option<usize>> {
+    let mut lengths: Vec<Option<usize>> = vec![None; sorted_numbers.len()];
+    for i in 0..sorted_numbers.len() {
+        if sorted_numbers[i] <= 3 {
+            lengths[i] = Some(1);
+        }
+        let mut j = i;
+        while j > 0 && sorted_numbers[i] - sorted_numbers[j - 1] <= 3 {
+            if let Some(prev) = lengths[j - 1] {
+                lengths[i] = Some(lengths[i].map_or(prev + 1, |cur| better(cur, prev + 1)));
+            }
+            j -= 1;
+        }
+    }
+    lengths
+}
+
+pub fn min_adapters_to_device(sorted_numbers: &[i32]) -> Option<usize> {
+    *chain_lengths(sorted_numbers, usize::min).last()?
+}
+
+pub fn longest_chain(sorted_numbers: &[i32]) -> Option<usize> {
+    *chain_lengths(sorted_numbers, usize::max).last()?
+}
+
+// The device stays rated for the full bag, so taking out the highest adapter (or any adapter
+// that bridges a gap) leaves no arrangements at all.
+pub fn arrangements_without_each(sorted_numbers: &[i32]) -> Vec<i64> {
+    let total = solve_part_two(sorted_numbers);
+    let from_outlet = ways_from_outlet(sorted_numbers);
+    let to_device = ways_to_device(sorted_numbers);
+    from_outlet
+        .iter()
+        .zip(to_device.iter())
+        .map(|(before, after)| total - before * after)
+        .collect::<Vec<_>>()
+}
+
+pub fn most_critical_adapter(sorted_numbers: &[i32]) -> Option<(i32, i64)> {
+    sorted_numbers
+        .iter()
+        .zip(arrangements_without_each(sorted_numbers))
+        .min_by_key(|(adapter, remaining)| (*remaining, **adapter))
+        .map(|(adapter, remaining)| (*adapter, remaining))
+}
+
+type Transfer = [[i64; 3]; 3];
+
+fn mul(left: &Transfer, right: &Transfer) -> Transfer {
+    let mut res = [[0; 3]; 3];
+    for (i, row) in res.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| left[i][k] * right[k][j]).sum();
+        }
+    }
+    res
+}
+
+// Counts chains between any two joltages in the bag, the outlet and the device included.
+// Adapters only ever connect to the three before them, so stepping one joltage forward is a
+// 3x3 transfer on (ways to this one, ways to the previous, ways to the one before that).
+// `jumps[l][i]` is the transfer from index i to index i + 2^l.
+pub struct ChainCounter {
+    joltages: Vec<i32>,
+    jumps: Vec<Vec<Transfer>>,
+}
+
+impl ChainCounter {
+    pub fn new(sorted_numbers: &[i32]) -> ChainCounter {
+        let mut joltages = vec![0];
+        joltages.extend_from_slice(sorted_numbers);
+        joltages.push(sorted_numbers.last().map_or(0, |max| *max) + 3);
+        let single_steps = (1..joltages.len())
+            .map(|to| {
+                let mut step = [[0, 0, 0], [1, 0, 0], [0, 1, 0]];
+                for back in 1..=3.min(to) {
+                    if joltages[to] - joltages[to - back] <= 3 {
+                        step[0][back - 1] = 1;
+                    }
+                }
+                step
+            })
+            .collect::<Vec<_>>();
+        let mut jumps = vec![single_steps];
+        let mut span = 1;
+        while span * 2 < joltages.len() {
+            let prev = jumps.last().unwrap();
+            let next = (0..prev.len() - span)
+                .map(|i| mul(&prev[i + span], &prev[i]))
+                .collect::<Vec<_>>();
+            jumps.push(next);
+            span *= 2;
+        }
+        ChainCounter { joltages, jumps }
+    }
+
+    pub fn count_between(&self, from: i32, to: i32) -> Option<i64> {
+        let mut at = self.joltages.binary_search(&from).ok()?;
+        let end = self.joltages.binary_search(&to).ok()?;
+        if at > end {
+            return Some(0);
+        }
+        let mut ways = [1, 0, 0];
+        for (level, jumps) in self.jumps.iter().enumerate().rev() {
+            while at + (1 << level) <= end {
+                let step = &jumps[at];
+                ways = [0, 1, 2].map(|i| (0..3).map(|k| step[i][k] * ways[k]).sum());
+                at += 1 << level;
+            }
+        }
+        Some(ways[0])
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::day10::*;
@@ -111,4 +250,59 @@ mod tests {
         assert_eq!(8, solve_part_two(&sorted_input(SAMPLE_INPUT)));
         assert_eq!(19208, solve_part_two(&sorted_input(SAMPLE_INPUT2)));
     }
+
+    #[test]
+    fn adapter_chain_lengths() {
+        assert_eq!(Some(8), min_adapters_to_device(&sorted_input(SAMPLE_INPUT)));
+        assert_eq!(Some(11), longest_chain(&sorted_input(SAMPLE_INPUT)));
+        assert_eq!(
+            Some(20),
+            min_adapters_to_device(&sorted_input(SAMPLE_INPUT2))
+        );
+        assert_eq!(Some(31), longest_chain(&sorted_input(SAMPLE_INPUT2)));
+        assert_eq!(None, min_adapters_to_device(&[1, 5, 6]));
+        assert_eq!(None, longest_chain(&[4, 5]));
+    }
+
+    #[test]
+    fn removing_single_adapters() {
+        let sorted = sorted_input(SAMPLE_INPUT);
+        assert_eq!(
+            vec![0, 0, 4, 4, 0, 0, 4, 0, 0, 0, 0],
+            arrangements_without_each(&sorted)
+        );
+        assert_eq!(Some((1, 0)), most_critical_adapter(&sorted));
+        assert_eq!(Vec::<i64>::new(), arrangements_without_each(&[]));
+        assert_eq!(None, most_critical_adapter(&[]));
+        assert_eq!(
+            vec![11, 10, 8, 10, 11, 0],
+            arrangements_without_each(&[1, 2, 3, 4, 5, 6])
+        );
+    }
+
+    #[test]
+    fn pairwise_chain_counts() {
+        let sorted = sorted_input(SAMPLE_INPUT2);
+        let counter = ChainCounter::new(&sorted);
+        assert_eq!(Some(19208), counter.count_between(0, 52));
+        assert_eq!(None, counter.count_between(0, 5));
+        assert_eq!(Some(0), counter.count_between(49, 1));
+
+        let mut joltages = vec![0];
+        joltages.extend_from_slice(&sorted);
+        joltages.push(52);
+        for (i, from) in joltages.iter().enumerate() {
+            let mut ways = vec![0; joltages.len()];
+            ways[i] = 1;
+            for j in i + 1..joltages.len() {
+                ways[j] = (i..j)
+                    .filter(|k| joltages[j] - joltages[*k] <= 3)
+                    .map(|k| ways[k])
+                    .sum();
+            }
+            for (j, to) in joltages.iter().enumerate().skip(i) {
+                assert_eq!(Some(ways[j]), counter.count_between(*from, *to));
+            }
+        }
+    }
 }
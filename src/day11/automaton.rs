@@ -0,0 +1,437 @@
+use super::{count_neighbours, GridStatus, NeighbourCounts, NeighbourTable};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// Anything that can work out, for every cell of a grid, which cells it looks at. Plain functions
+// like `neighbour_coords_for` and `visible_neighbour_coords_for` already are one.
+pub trait Neighbourhood {
+    fn coords_for(&self, grid: &[Vec<GridStatus>]) -> NeighbourTable;
+}
+
+impl<F> Neighbourhood for F
+where
+    F: Fn(&[Vec<GridStatus>]) -> NeighbourTable,
+{
+    fn coords_for(&self, grid: &[Vec<GridStatus>]) -> NeighbourTable {
+        self(grid)
+    }
+}
+
+pub enum Rule {
+    // Empty seats are dead cells and taken seats are live ones; floor never changes.
+    LifeLike {
+        birth: [bool; 9],
+        survive: [bool; 9],
+    },
+    Custom(Box<dyn Fn(GridStatus, NeighbourCounts) -> GridStatus + Send + Sync>),
+}
+
+impl Rule {
+    pub fn parse(rule: &str) -> Result<Rule, String> {
+        let mut birth = None;
+        let mut survive = None;
+        for part in rule.trim().split('/') {
+            let mut chars = part.chars();
+            let target = match chars.next() {
+                Some('B') | Some('b') => &mut birth,
+                Some('S') | Some('s') => &mut survive,
+                _ => return Err(format!("expected B or S at the start of {:?}", part)),
+            };
+            if target.is_some() {
+                return Err(format!("{:?} given twice in {:?}", &part[..1], rule));
+            }
+            let mut counts = [false; 9];
+            for ch in chars {
+                match ch.to_digit(10) {
+                    Some(n) if n <= 8 => counts[n as usize] = true,
+                    _ => return Err(format!("bad neighbour count {:?} in {:?}", ch, rule)),
+                }
+            }
+            *target = Some(counts);
+        }
+        match (birth, survive) {
+            (Some(birth), Some(survive)) => Ok(Rule::LifeLike { birth, survive }),
+            _ => Err(format!("{:?} needs both a B and an S part", rule)),
+        }
+    }
+
+    pub fn custom(
+        rule: impl Fn(GridStatus, NeighbourCounts) -> GridStatus + Send + Sync + 'static,
+    ) -> Rule {
+        Rule::Custom(Box::new(rule))
+    }
+
+    pub fn apply(&self, cell: GridStatus, counts: NeighbourCounts) -> GridStatus {
+        match self {
+            Rule::LifeLike { birth, survive } => match cell {
+                GridStatus::Floor => GridStatus::Floor,
+                // counts past 8 (from a bigger neighbourhood) never match
+                GridStatus::Empty if in_rule(birth, counts.taken) => GridStatus::Taken,
+                GridStatus::Taken if !in_rule(survive, counts.taken) => GridStatus::Empty,
+                other => other,
+            },
+            Rule::Custom(rule) => rule(cell, counts),
+        }
+    }
+}
+
+fn in_rule(counts: &[bool; 9], taken: u32) -> bool {
+    counts.get(taken as usize).copied().unwrap_or(false)
+}
+
+pub struct Automaton {
+    rule: Rule,
+    neighbourhood: Box<dyn Neighbourhood>,
+    threads: usize,
+}
+
+// Works out rows of the next generation, `rows` starting at row `first_row`. Returns whether any
+// of them changed.
+fn step_rows(
+    rule: &Rule,
+    front: &[Vec<GridStatus>],
+    rows: &mut [Vec<GridStatus>],
+    neighbour_coords: &NeighbourTable,
+    first_row: usize,
+) -> bool {
+    let mut changed = false;
+    for (row, back_row) in (first_row..).zip(rows.iter_mut()) {
+        for (col, cell) in back_row.iter_mut().enumerate() {
+            let counts = count_neighbours(front, neighbour_coords, col, row);
+            *cell = rule.apply(front[row][col], counts);
+            changed |= *cell != front[row][col];
+        }
+    }
+    changed
+}
+
+impl Automaton {
+    pub fn new(rule: Rule, neighbourhood: impl Neighbourhood + 'static) -> Automaton {
+        Automaton {
+            rule,
+            neighbourhood: Box::new(neighbourhood),
+            threads: 1,
+        }
+    }
+
+    // Splits each generation into bands of rows worked out on this many threads.
+    pub fn with_threads(mut self, threads: usize) -> Automaton {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn seating_p1() -> Automaton {
+        Automaton::new(
+            Rule::parse("B0/S0123").unwrap(),
+            super::neighbour_coords_for,
+        )
+    }
+
+    pub fn seating_p2() -> Automaton {
+        Automaton::new(
+            Rule::parse("B0/S01234").unwrap(),
+            super::visible_neighbour_coords_for,
+        )
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn neighbour_coords(&self, grid: &[Vec<GridStatus>]) -> NeighbourTable {
+        self.neighbourhood.coords_for(grid)
+    }
+
+    // Returns whether anything changed.
+    pub fn step(
+        &self,
+        front: &[Vec<GridStatus>],
+        back: &mut [Vec<GridStatus>],
+        neighbour_coords: &NeighbourTable,
+    ) -> bool {
+        let rule = &self.rule;
+        if self.threads == 1 {
+            return step_rows(rule, front, back, neighbour_coords, 0);
+        }
+        let band = back.len().div_ceil(self.threads).max(1);
+        std::thread::scope(|scope| {
+            back.chunks_mut(band)
+                .enumerate()
+                .map(|(i, rows)| {
+                    scope.spawn(move || step_rows(rule, front, rows, neighbour_coords, i * band))
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|band| band.join().expect("stepping thread panicked"))
+                .collect::<Vec<_>>()
+                .contains(&true)
+        })
+    }
+
+    // `generations` steps on from `grid`, keeping just two generations at a time.
+    fn advance(
+        &self,
+        mut front: Vec<Vec<GridStatus>>,
+        generations: u64,
+        neighbour_coords: &NeighbourTable,
+    ) -> Vec<Vec<GridStatus>> {
+        let mut back = front.clone();
+        for _ in 0..generations {
+            if !self.step(&front, &mut back, neighbour_coords) {
+                break;
+            }
+            std::mem::swap(&mut front, &mut back);
+        }
+        front
+    }
+
+    // Steps until some generation repeats. Only a hash of each generation is kept: when one turns
+    // up again, the earlier generation is worked out afresh from the starting grid to make sure
+    // they really are the same. The neighbourhood is worked out once from the starting grid.
+    pub fn find_cycle(&self, grid: Vec<Vec<GridStatus>>) -> Cycle<'_> {
+        let neighbour_coords = self.neighbour_coords(&grid);
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut front = grid.clone();
+        let mut back = grid.clone();
+        let mut generation = 0;
+        loop {
+            let mut hasher = DefaultHasher::new();
+            front.hash(&mut hasher);
+            let same_hash = seen.entry(hasher.finish()).or_default();
+            let repeated = same_hash
+                .iter()
+                .find(|g| self.advance(grid.clone(), **g as u64, &neighbour_coords) == front);
+            let (transient, period) = match repeated {
+                Some(start) => (*start, generation - start),
+                None if !self.step(&front, &mut back, &neighbour_coords) => (generation, 1),
+                None => {
+                    same_hash.push(generation);
+                    std::mem::swap(&mut front, &mut back);
+                    generation += 1;
+                    continue;
+                }
+            };
+            return Cycle {
+                transient,
+                period,
+                automaton: self,
+                neighbour_coords,
+                first: grid,
+                looped: front,
+            };
+        }
+    }
+
+    // None if the grid settles into a cycle longer than one generation instead. Besides the two
+    // generations being stepped between, only the one Brent's algorithm compares against is kept.
+    pub fn run_to_fixed_point(&self, grid: Vec<Vec<GridStatus>>) -> Option<Vec<Vec<GridStatus>>> {
+        let neighbour_coords = self.neighbour_coords(&grid);
+        let mut front = grid;
+        let mut back = front.clone();
+        let mut saved = front.clone();
+        let (mut power, mut lap) = (1, 0);
+        while self.step(&front, &mut back, &neighbour_coords) {
+            std::mem::swap(&mut front, &mut back);
+            // something changed, so a repeat is a cycle longer than one generation
+            if front == saved {
+                return None;
+            }
+            lap += 1;
+            if lap == power {
+                saved.clone_from(&front);
+                power *= 2;
+                lap = 0;
+            }
+        }
+        Some(front)
+    }
+
+    pub fn state_at(&self, grid: Vec<Vec<GridStatus>>, generation: u64) -> Vec<Vec<GridStatus>> {
+        self.find_cycle(grid).state_at(generation)
+    }
+}
+
+// Generations `transient..transient + period` repeat forever. Only the starting grid and the first
+// generation of the loop are kept; any other is worked out again from one of them.
+pub struct Cycle<'a> {
+    pub transient: usize,
+    pub period: usize,
+    automaton: &'a Automaton,
+    neighbour_coords: NeighbourTable,
+    first: Vec<Vec<GridStatus>>,
+    looped: Vec<Vec<GridStatus>>,
+}
+
+impl Cycle<'_> {
+    pub fn state_at(&self, generation: u64) -> Vec<Vec<GridStatus>> {
+        let transient = self.transient as u64;
+        let (from, steps) = if generation < transient {
+            (&self.first, generation)
+        } else {
+            (&self.looped, (generation - transient) % self.period as u64)
+        };
+        self.automaton
+            .advance(from.clone(), steps, &self.neighbour_coords)
+    }
+
+    // Every generation in turn from the starting grid, stepping once for each.
+    pub fn generations(&self) -> impl Iterator<Item = Vec<Vec<GridStatus>>> + '_ {
+        std::iter::successors(Some(self.first.clone()), move |grid| {
+            Some(
+                self.automaton
+                    .advance(grid.clone(), 1, &self.neighbour_coords),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day11::tests::generated_seat_map;
+    use crate::day11::{count_taken, neighbour_coords_for, to_grid_status, Boundary};
+
+    #[test]
+    fn parse_rule_strings() {
+        match Rule::parse("B3/S23").unwrap() {
+            Rule::LifeLike { birth, survive } => {
+                assert_eq!(vec![3], (0..9).filter(|n| birth[*n]).collect::<Vec<_>>());
+                assert_eq!(
+                    vec![2, 3],
+                    (0..9).filter(|n| survive[*n]).collect::<Vec<_>>()
+                );
+            }
+            Rule::Custom(_) => panic!("expected a life-like rule"),
+        }
+        assert!(Rule::parse("s23/b36").is_ok());
+        assert!(Rule::parse("B3").is_err());
+        assert!(Rule::parse("B3/S29").is_err());
+        assert!(Rule::parse("B3/B2").is_err());
+        assert!(Rule::parse("X3/S23").is_err());
+    }
+
+    #[test]
+    fn blinker_under_life() {
+        let life = Automaton::new(Rule::parse("B3/S23").unwrap(), neighbour_coords_for);
+        let grid = to_grid_status("LLLLL\nLL#LL\nLL#LL\nLL#LL\nLLLLL");
+        let coords = life.neighbour_coords(&grid);
+        let mut next = grid.clone();
+        life.step(&grid, &mut next, &coords);
+        assert_eq!(to_grid_status("LLLLL\nLLLLL\nL###L\nLLLLL\nLLLLL"), next);
+        let mut after = grid.clone();
+        life.step(&next, &mut after, &coords);
+        assert_eq!(grid, after);
+    }
+
+    #[test]
+    fn bigger_neighbourhoods() {
+        // everything within two cells, 24 neighbours in the middle of the grid
+        let radius_two = |grid: &[Vec<GridStatus>]| {
+            let (height, width) = (grid.len() as i64, grid[0].len() as i64);
+            NeighbourTable::build(grid, Boundary::Clip, |y, x, cells| {
+                for (row, col) in (-2..=2).flat_map(|dy| (-2..=2).map(move |dx| (dy, dx))) {
+                    let (row, col) = (y as i64 + row, x as i64 + col);
+                    if (row, col) != (y as i64, x as i64)
+                        && (0..height).contains(&row)
+                        && (0..width).contains(&col)
+                    {
+                        cells.push((row * width + col) as u32);
+                    }
+                }
+                0
+            })
+        };
+        let life = Automaton::new(Rule::parse("B3/S23").unwrap(), radius_two);
+        let grid = to_grid_status("#####\n#####\n#####\n#####\n#####");
+        let coords = life.neighbour_coords(&grid);
+        assert_eq!(24, coords.of(12).len());
+        let mut next = grid.clone();
+        life.step(&grid, &mut next, &coords);
+        assert_eq!(to_grid_status("LLLLL\nLLLLL\nLLLLL\nLLLLL\nLLLLL"), next);
+    }
+
+    #[test]
+    fn custom_rule_matches_life_like() {
+        let starting = to_grid_status(
+            "L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL",
+        );
+        let custom = Automaton::new(
+            Rule::custom(|cell, counts| match cell {
+                GridStatus::Empty if counts.taken == 0 => GridStatus::Taken,
+                GridStatus::Taken if counts.taken >= 4 => GridStatus::Empty,
+                other => other,
+            }),
+            neighbour_coords_for,
+        );
+        assert_eq!(
+            37,
+            count_taken(&custom.run_to_fixed_point(starting.clone()).unwrap())
+        );
+        assert_eq!(
+            26,
+            count_taken(
+                &Automaton::seating_p2()
+                    .run_to_fixed_point(starting)
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn oscillators_are_detected() {
+        let life = Automaton::new(Rule::parse("B3/S23").unwrap(), neighbour_coords_for);
+        let blinker = to_grid_status("LLLLL\nLL#LL\nLL#LL\nLL#LL\nLLLLL");
+        let cycle = life.find_cycle(blinker.clone());
+        assert_eq!((0, 2), (cycle.transient, cycle.period));
+        assert_eq!(blinker, cycle.state_at(1_000_000_000_000));
+        assert_eq!(
+            to_grid_status("LLLLL\nLLLLL\nL###L\nLLLLL\nLLLLL"),
+            life.state_at(blinker.clone(), 1_000_000_000_001)
+        );
+        assert_eq!(None, life.run_to_fixed_point(blinker));
+
+        // an L-tromino grows into a block, which then stays put
+        let tromino = to_grid_status("LLLL\nL#LL\nL##L\nLLLL");
+        let cycle = life.find_cycle(tromino.clone());
+        assert_eq!((1, 1), (cycle.transient, cycle.period));
+        assert_eq!(tromino, cycle.state_at(0));
+        assert_eq!(
+            to_grid_status("LLLL\nL##L\nL##L\nLLLL"),
+            life.state_at(tromino, 12345)
+        );
+    }
+
+    #[test]
+    fn threaded_steps_match_single_threaded() {
+        for (seed, (width, height)) in [(1, 1), (12, 5), (40, 31)].iter().enumerate() {
+            let grid = to_grid_status(&generated_seat_map(*width, *height, seed as u64));
+            for threads in [2, 3, 7, 64].iter() {
+                let seatings: [fn() -> Automaton; 2] =
+                    [Automaton::seating_p1, Automaton::seating_p2];
+                for seating in seatings.iter() {
+                    let (single, threaded) = (seating(), seating().with_threads(*threads));
+                    let expected = single.find_cycle(grid.clone());
+                    let got = threaded.find_cycle(grid.clone());
+                    assert_eq!(
+                        (expected.transient, expected.period),
+                        (got.transient, got.period)
+                    );
+                    let lap = expected.transient + expected.period;
+                    for (expected, got) in expected.generations().zip(got.generations()).take(lap) {
+                        assert_eq!(expected, got);
+                    }
+                }
+            }
+        }
+    }
+}
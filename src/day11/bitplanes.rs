@@ -0,0 +1,263 @@
+use super::automaton::Rule;
+use super::GridStatus;
+
+// One bit per cell, 64 cells to a word, each row padded out to whole words. Bit c of word w in a
+// row is column 64 * w + c; the padding bits are always clear in every plane.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BitPlanes {
+    width: usize,
+    stride: usize,
+    floor: Vec<u64>,
+    empty: Vec<u64>,
+    taken: Vec<u64>,
+}
+
+impl BitPlanes {
+    fn from_grid(grid: &[Vec<GridStatus>]) -> BitPlanes {
+        let width = grid.first().map_or(0, Vec::len);
+        let stride = width.div_ceil(64);
+        let mut planes = BitPlanes {
+            width,
+            stride,
+            floor: vec![0; stride * grid.len()],
+            empty: vec![0; stride * grid.len()],
+            taken: vec![0; stride * grid.len()],
+        };
+        for (row, line) in grid.iter().enumerate() {
+            for (col, cell) in line.iter().enumerate() {
+                let plane = match cell {
+                    GridStatus::Floor => &mut planes.floor,
+                    GridStatus::Empty => &mut planes.empty,
+                    GridStatus::Taken => &mut planes.taken,
+                };
+                plane[row * stride + col / 64] |= 1 << (col % 64);
+            }
+        }
+        planes
+    }
+
+    fn to_grid(&self) -> Vec<Vec<GridStatus>> {
+        (0..self.floor.len() / self.stride.max(1))
+            .map(|row| {
+                (0..self.width)
+                    .map(|col| {
+                        let word = row * self.stride + col / 64;
+                        let bit = 1 << (col % 64);
+                        if self.taken[word] & bit != 0 {
+                            GridStatus::Taken
+                        } else if self.empty[word] & bit != 0 {
+                            GridStatus::Empty
+                        } else {
+                            GridStatus::Floor
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    }
+}
+
+// Adds one bit to each of 64 four-bit counters held across `counter`, ripple-carry style.
+fn add_bit(counter: &mut [u64; 4], bit: u64) {
+    let mut carry = bit;
+    for digit in counter.iter_mut() {
+        let next_carry = *digit & carry;
+        *digit ^= carry;
+        carry = next_carry;
+    }
+}
+
+// Lanes of `counter` whose count is one of the flagged values.
+fn count_in(counter: &[u64; 4], wanted: &[bool; 9]) -> u64 {
+    (0..9).filter(|n| wanted[*n]).fold(0, |acc, n| {
+        acc | counter.iter().enumerate().fold(!0, |lanes, (digit, bits)| {
+            lanes & if n >> digit & 1 == 1 { *bits } else { !*bits }
+        })
+    })
+}
+
+// Runs a life-like rule over the adjacent neighbourhood (what `neighbour_coords_for` gives) with
+// 64 cells per word. Other rules and neighbourhoods aren't supported.
+#[derive(Clone)]
+pub struct BitSim {
+    birth: [bool; 9],
+    survive: [bool; 9],
+    front: BitPlanes,
+    back: BitPlanes,
+    generation: usize,
+}
+
+impl BitSim {
+    pub fn new(rule: &Rule, grid: &[Vec<GridStatus>]) -> Option<BitSim> {
+        match rule {
+            Rule::LifeLike { birth, survive } => {
+                let front = BitPlanes::from_grid(grid);
+                Some(BitSim {
+                    birth: *birth,
+                    survive: *survive,
+                    back: front.clone(),
+                    front,
+                    generation: 0,
+                })
+            }
+            Rule::Custom(_) => None,
+        }
+    }
+
+    // Returns whether anything changed.
+    pub fn step(&mut self) -> bool {
+        let stride = self.front.stride;
+        let rows = self.front.floor.len() / stride.max(1);
+        let taken = &self.front.taken;
+        let word_at = |row: usize, word: isize| -> u64 {
+            if word < 0 || word as usize >= stride {
+                0
+            } else {
+                taken[row * stride + word as usize]
+            }
+        };
+        let mut changed = false;
+        for row in 0..rows {
+            let neighbour_rows = [row.checked_sub(1), Some(row), Some(row + 1)];
+            for word in 0..stride {
+                let mut counter = [0; 4];
+                for other in neighbour_rows
+                    .iter()
+                    .filter_map(|r| *r)
+                    .filter(|r| *r < rows)
+                {
+                    let here = word_at(other, word as isize);
+                    let west = here << 1 | word_at(other, word as isize - 1) >> 63;
+                    let east = here >> 1 | word_at(other, word as isize + 1) << 63;
+                    if other != row {
+                        add_bit(&mut counter, here);
+                    }
+                    add_bit(&mut counter, west);
+                    add_bit(&mut counter, east);
+                }
+                let at = row * stride + word;
+                let (empty, taken) = (self.front.empty[at], self.front.taken[at]);
+                let now_taken = empty & count_in(&counter, &self.birth)
+                    | taken & count_in(&counter, &self.survive);
+                let now_empty = (empty | taken) & !now_taken;
+                changed |= now_taken != taken;
+                self.back.taken[at] = now_taken;
+                self.back.empty[at] = now_empty;
+            }
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+        self.generation += 1;
+        changed
+    }
+
+    // Same contract as `FrontierSim::run_to_fixed_point`.
+    pub fn run_to_fixed_point(&mut self, max_rounds: usize) -> Option<usize> {
+        while self.generation < max_rounds {
+            if !self.step() {
+                return Some(self.generation - 1);
+            }
+        }
+        None
+    }
+
+    pub fn grid(&self) -> Vec<Vec<GridStatus>> {
+        self.front.to_grid()
+    }
+
+    pub fn count_taken(&self) -> i32 {
+        self.front
+            .taken
+            .iter()
+            .map(|word| word.count_ones() as i32)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use crate::day11::automaton::Automaton;
+    use crate::day11::tests::generated_seat_map;
+    use crate::day11::{count_taken, neighbour_coords_for, to_grid_status};
+    use test::Bencher;
+
+    #[test]
+    fn planes_round_trip() {
+        let grid = to_grid_status(&generated_seat_map(130, 7, 1));
+        assert_eq!(grid, BitPlanes::from_grid(&grid).to_grid());
+    }
+
+    #[test]
+    fn matches_automaton_step_for_step() {
+        for rule in ["B0/S0123", "B3/S23", "B36/S23", "B2/S", "B012345678/S8"].iter() {
+            let automaton = Automaton::new(Rule::parse(rule).unwrap(), neighbour_coords_for);
+            for (seed, (width, height)) in [(1, 1), (5, 3), (63, 20), (64, 9), (65, 11), (130, 40)]
+                .iter()
+                .enumerate()
+            {
+                let mut grid = to_grid_status(&generated_seat_map(*width, *height, seed as u64));
+                // scatter some taken seats so births and deaths both happen from the start
+                for (i, cell) in grid.iter_mut().flatten().enumerate() {
+                    if i % 3 == 0 && *cell == GridStatus::Empty {
+                        *cell = GridStatus::Taken;
+                    }
+                }
+                let coords = automaton.neighbour_coords(&grid);
+                let mut sim = BitSim::new(automaton.rule(), &grid).unwrap();
+                let mut back = grid.clone();
+                for _ in 0..30 {
+                    automaton.step(&grid, &mut back, &coords);
+                    std::mem::swap(&mut grid, &mut back);
+                    assert_eq!(grid != back, sim.step());
+                    assert_eq!(grid, sim.grid(), "{} on {}x{}", rule, width, height);
+                    assert_eq!(count_taken(&grid), sim.count_taken());
+                }
+            }
+        }
+        assert!(BitSim::new(&Rule::custom(|cell, _| cell), &[]).is_none());
+    }
+
+    #[test]
+    fn solves_first_part() {
+        let input =
+            std::fs::read_to_string("src/day11/input.in").expect("failed to read day11 input");
+        let mut sim = BitSim::new(Automaton::seating_p1().rule(), &to_grid_status(&input)).unwrap();
+        assert!(sim.run_to_fixed_point(1000).is_some());
+        assert_eq!(2261, sim.count_taken());
+    }
+
+    fn huge_map() -> Vec<Vec<GridStatus>> {
+        to_grid_status(&generated_seat_map(500, 500, 3))
+    }
+
+    #[bench]
+    fn bench_automaton_steps_huge(b: &mut Bencher) {
+        let grid = huge_map();
+        let automaton = Automaton::seating_p1();
+        let coords = automaton.neighbour_coords(&grid);
+        b.iter(|| {
+            let (mut front, mut back) = (grid.clone(), grid.clone());
+            for _ in 0..10 {
+                automaton.step(&front, &mut back, &coords);
+                std::mem::swap(&mut front, &mut back);
+            }
+            front
+        });
+    }
+
+    #[bench]
+    fn bench_bitplane_steps_huge(b: &mut Bencher) {
+        let grid = huge_map();
+        let rule = Rule::parse("B0/S0123").unwrap();
+        let sim = BitSim::new(&rule, &grid).unwrap();
+        b.iter(|| {
+            let mut sim = sim.clone();
+            for _ in 0..10 {
+                sim.step();
+            }
+            sim.count_taken()
+        });
+    }
+}
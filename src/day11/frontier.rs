@@ -0,0 +1,198 @@
+use super::automaton::Automaton;
+use super::{GridStatus, NeighbourCounts, NeighbourTable};
+
+// Same rules as `Automaton::step`, but cells live in one flat array and each round only looks at
+// cells that changed last round or that watch a cell that did. Once a round changes nothing the
+// grid has settled. While a big share of the grid is still flipping every round, keeping track of
+// who to look at costs more than just looking at everything, so those rounds go over every cell.
+pub struct FrontierSim<'a> {
+    automaton: &'a Automaton,
+    width: usize,
+    cells: Vec<GridStatus>,
+    neighbours: NeighbourTable,
+    watchers: NeighbourTable,
+    // None means every cell
+    active: Option<Vec<usize>>,
+    queued_in: Vec<usize>,
+    generation: usize,
+}
+
+// Above one change in this many cells the next round goes over everything.
+const DENSE_RATIO: usize = 8;
+
+impl<'a> FrontierSim<'a> {
+    pub fn new(automaton: &'a Automaton, grid: &[Vec<GridStatus>]) -> FrontierSim<'a> {
+        let width = grid.first().map_or(0, Vec::len);
+        let cells = grid.iter().flatten().copied().collect::<Vec<_>>();
+        let neighbours = automaton.neighbour_coords(grid);
+        let watchers = neighbours.watchers();
+        FrontierSim {
+            automaton,
+            width,
+            active: None,
+            queued_in: vec![0; cells.len()],
+            cells,
+            neighbours,
+            watchers,
+            generation: 0,
+        }
+    }
+
+    fn counts_for(&self, cell: usize) -> NeighbourCounts {
+        let mut by_state = [0; 3];
+        for neighbour in self.neighbours.of(cell) {
+            by_state[self.cells[*neighbour as usize] as usize] += 1;
+        }
+        let (border_state, border_count) = self.neighbours.border_of(cell);
+        by_state[border_state as usize] += border_count;
+        NeighbourCounts {
+            floor: by_state[GridStatus::Floor as usize],
+            empty: by_state[GridStatus::Empty as usize],
+            taken: by_state[GridStatus::Taken as usize],
+        }
+    }
+
+    // Returns how many cells changed.
+    pub fn step(&mut self) -> usize {
+        let rule = self.automaton.rule();
+        let mut changes = vec![];
+        let mut visit = |cell: usize| {
+            let next = rule.apply(self.cells[cell], self.counts_for(cell));
+            if next != self.cells[cell] {
+                changes.push((cell, next));
+            }
+        };
+        match &self.active {
+            Some(active) => active.iter().for_each(|cell| visit(*cell)),
+            None => (0..self.cells.len()).for_each(visit),
+        }
+        self.generation += 1;
+        for (cell, next) in changes.iter() {
+            self.cells[*cell] = *next;
+        }
+        if changes.len() * DENSE_RATIO > self.cells.len() {
+            self.active = None;
+            return changes.len();
+        }
+        let mut active = self.active.take().unwrap_or_default();
+        active.clear();
+        for (cell, _) in changes.iter() {
+            let watchers = self.watchers.of(*cell).iter().map(|w| *w as usize);
+            for affected in watchers.chain(std::iter::once(*cell)) {
+                if self.queued_in[affected] != self.generation {
+                    self.queued_in[affected] = self.generation;
+                    active.push(affected);
+                }
+            }
+        }
+        self.active = Some(active);
+        changes.len()
+    }
+
+    // Returns the number of rounds it took to settle, or None if it is still changing after
+    // `max_rounds` (seating can oscillate; `Automaton::find_cycle` deals with that).
+    pub fn run_to_fixed_point(&mut self, max_rounds: usize) -> Option<usize> {
+        while self.generation < max_rounds {
+            if self.step() == 0 {
+                return Some(self.generation - 1);
+            }
+        }
+        None
+    }
+
+    pub fn grid(&self) -> Vec<Vec<GridStatus>> {
+        self.cells
+            .chunks(self.width.max(1))
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>()
+    }
+
+    pub fn count_taken(&self) -> i32 {
+        self.cells
+            .iter()
+            .filter(|cell| **cell == GridStatus::Taken)
+            .count() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use crate::day11::tests::generated_seat_map;
+    use crate::day11::{count_taken, to_grid_status};
+    use test::Bencher;
+
+    #[test]
+    fn frontier_matches_full_rescan() {
+        for (seed, (width, height)) in [(3, 4), (40, 30), (97, 101)].iter().enumerate() {
+            let grid = to_grid_status(&generated_seat_map(*width, *height, seed as u64));
+            for automaton in [Automaton::seating_p1(), Automaton::seating_p2()].iter() {
+                let cycle = automaton.find_cycle(grid.clone());
+                let mut sim = FrontierSim::new(automaton, &grid);
+                let lap = cycle.transient + cycle.period;
+                for state in cycle.generations().skip(1).take(lap) {
+                    sim.step();
+                    assert_eq!(state, sim.grid());
+                    assert_eq!(count_taken(&sim.grid()), sim.count_taken());
+                }
+                let mut sim = FrontierSim::new(automaton, &grid);
+                let rounds = sim.run_to_fixed_point(cycle.transient + cycle.period + 1);
+                if cycle.period == 1 {
+                    assert_eq!(Some(cycle.transient), rounds);
+                } else {
+                    assert_eq!(None, rounds);
+                }
+            }
+        }
+    }
+
+    fn large_map() -> Vec<Vec<GridStatus>> {
+        to_grid_status(&generated_seat_map(150, 150, 7))
+    }
+
+    // A settled large map with a handful of seats kicked out of place.
+    fn nearly_settled_map() -> Vec<Vec<GridStatus>> {
+        let mut grid = Automaton::seating_p2()
+            .run_to_fixed_point(large_map())
+            .unwrap();
+        for i in 0..10 {
+            let cell = &mut grid[i * 15 + 3][i * 13 + 5];
+            *cell = match *cell {
+                GridStatus::Empty => GridStatus::Taken,
+                GridStatus::Taken => GridStatus::Empty,
+                GridStatus::Floor => GridStatus::Floor,
+            };
+        }
+        grid
+    }
+
+    #[bench]
+    fn bench_full_rescan_large(b: &mut Bencher) {
+        let grid = large_map();
+        let automaton = Automaton::seating_p2();
+        b.iter(|| automaton.run_to_fixed_point(grid.clone()));
+    }
+
+    #[bench]
+    fn bench_frontier_large(b: &mut Bencher) {
+        let grid = large_map();
+        let automaton = Automaton::seating_p2();
+        b.iter(|| FrontierSim::new(&automaton, &grid).run_to_fixed_point(usize::MAX));
+    }
+
+    #[bench]
+    fn bench_full_rescan_nearly_settled(b: &mut Bencher) {
+        let grid = nearly_settled_map();
+        let automaton = Automaton::seating_p2();
+        b.iter(|| automaton.run_to_fixed_point(grid.clone()));
+    }
+
+    #[bench]
+    fn bench_frontier_nearly_settled(b: &mut Bencher) {
+        let grid = nearly_settled_map();
+        let automaton = Automaton::seating_p2();
+        b.iter(|| FrontierSim::new(&automaton, &grid).run_to_fixed_point(usize::MAX));
+    }
+}
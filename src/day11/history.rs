@@ -0,0 +1,135 @@
+use super::automaton::Automaton;
+use super::{count_taken, GridStatus};
+
+// What happened to every seat over a run: how often it flipped and when it last did, plus how
+// many seats were taken in each generation (generation 0 being the starting grid).
+#[derive(Debug, PartialEq, Eq)]
+pub struct RunHistory {
+    pub seats: Vec<Vec<bool>>,
+    pub flips: Vec<Vec<u32>>,
+    pub last_changed: Vec<Vec<Option<usize>>>,
+    pub occupancy: Vec<i32>,
+}
+
+impl RunHistory {
+    // Stops early once a generation changes nothing.
+    pub fn record(
+        automaton: &Automaton,
+        grid: Vec<Vec<GridStatus>>,
+        max_generations: usize,
+    ) -> RunHistory {
+        let neighbour_coords = automaton.neighbour_coords(&grid);
+        let (height, width) = (grid.len(), grid.first().map_or(0, Vec::len));
+        let mut history = RunHistory {
+            seats: grid
+                .iter()
+                .map(|row| row.iter().map(|cell| *cell != GridStatus::Floor).collect())
+                .collect(),
+            flips: vec![vec![0; width]; height],
+            last_changed: vec![vec![None; width]; height],
+            occupancy: vec![count_taken(&grid)],
+        };
+        let mut back = grid.clone();
+        let mut front = grid;
+        for generation in 1..=max_generations {
+            if !automaton.step(&front, &mut back, &neighbour_coords) {
+                break;
+            }
+            std::mem::swap(&mut front, &mut back);
+            for (row, (now, before)) in front.iter().zip(back.iter()).enumerate() {
+                for (col, _) in now
+                    .iter()
+                    .zip(before)
+                    .enumerate()
+                    .filter(|(_, (a, b))| a != b)
+                {
+                    history.flips[row][col] += 1;
+                    history.last_changed[row][col] = Some(generation);
+                }
+            }
+            history.occupancy.push(count_taken(&front));
+        }
+        history
+    }
+
+    // One line per seat; seats that never changed have an empty last_changed.
+    pub fn seats_csv(&self) -> String {
+        let mut csv = String::from("row,col,flips,last_changed\n");
+        for (row, seats) in self.seats.iter().enumerate() {
+            for (col, _) in seats.iter().enumerate().filter(|(_, seat)| **seat) {
+                csv.push_str(&format!(
+                    "{},{},{},{}\n",
+                    row,
+                    col,
+                    self.flips[row][col],
+                    self.last_changed[row][col].map_or(String::new(), |g| g.to_string())
+                ));
+            }
+        }
+        csv
+    }
+
+    pub fn occupancy_csv(&self) -> String {
+        let mut csv = String::from("generation,taken\n");
+        for (generation, taken) in self.occupancy.iter().enumerate() {
+            csv.push_str(&format!("{},{}\n", generation, taken));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day11::automaton::Rule;
+    use crate::day11::{neighbour_coords_for, to_grid_status};
+
+    #[test]
+    fn record_small_run() {
+        // L.L   #.#   #.#
+        // LLL   ###   #L#
+        // L.L   #.#   #.#
+        let history = RunHistory::record(
+            &Automaton::seating_p1(),
+            to_grid_status("L.L\nLLL\nL.L"),
+            100,
+        );
+        assert_eq!(vec![0, 7, 6], history.occupancy);
+        assert_eq!(
+            vec![vec![1, 0, 1], vec![1, 2, 1], vec![1, 0, 1]],
+            history.flips
+        );
+        assert_eq!(
+            vec![
+                vec![Some(1), None, Some(1)],
+                vec![Some(1), Some(2), Some(1)],
+                vec![Some(1), None, Some(1)]
+            ],
+            history.last_changed
+        );
+        assert_eq!(
+            "row,col,flips,last_changed
+0,0,1,1
+0,2,1,1
+1,0,1,1
+1,1,2,2
+1,2,1,1
+2,0,1,1
+2,2,1,1
+",
+            history.seats_csv()
+        );
+        assert_eq!("generation,taken\n0,0\n1,7\n2,6\n", history.occupancy_csv());
+    }
+
+    #[test]
+    fn oscillators_stop_at_the_limit() {
+        let life = Automaton::new(Rule::parse("B3/S23").unwrap(), neighbour_coords_for);
+        let history = RunHistory::record(&life, to_grid_status("LLL\n###\nLLL"), 5);
+        assert_eq!(vec![3; 6], history.occupancy);
+        assert_eq!(5, history.flips[0][1]);
+        assert_eq!(Some(5), history.last_changed[1][0]);
+        assert_eq!(0, history.flips[1][1]);
+        assert_eq!(None, history.last_changed[1][1]);
+    }
+}
@@ -1,105 +1,421 @@
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum GridStatus {
+pub mod automaton;
+pub mod bitplanes;
+pub mod frontier;
+pub mod history;
+pub mod planner;
+pub mod predecessor;
+pub mod render;
+
+use crate::day13::number_theory::gcd;
+use automaton::{Automaton, Rule};
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GridStatus {
     Floor,
     Empty,
     Taken,
 }
 
-fn to_grid_status(input: &str) -> Vec<Vec<GridStatus>> {
-    input
-        .split_whitespace()
-        .map(|line| {
-            line.chars()
-                .map(|ch| match ch {
-                    '.' => GridStatus::Floor,
-                    'L' => GridStatus::Empty,
-                    '#' => GridStatus::Taken,
-                    _ => panic!("bruh"),
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>()
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NeighbourCounts {
+    pub floor: u32,
+    pub empty: u32,
+    pub taken: u32,
+}
+
+impl NeighbourCounts {
+    fn add(&mut self, state: GridStatus, count: u32) {
+        match state {
+            GridStatus::Floor => self.floor += count,
+            GridStatus::Empty => self.empty += count,
+            GridStatus::Taken => self.taken += count,
+        }
+    }
+}
+
+// What lies past the edges of the grid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    // nothing; neighbours stop at the edge
+    Clip,
+    // rows and columns wrap around
+    Torus,
+    // every cell off the grid is in this state
+    Constant(GridStatus),
+    // the grid is mirrored about its edge cells, so the cell just past an edge is the one just
+    // inside it and no cell is its own neighbour. A grid one cell high (wide) has nothing above or
+    // below (left or right), and a line of sight that bounces back onto its own cell stops there.
+    Reflect,
+}
+
+impl Boundary {
+    // Where (y, x) ends up on the grid, if anywhere.
+    fn resolve(self, y: i64, x: i64, height: usize, width: usize) -> Option<(usize, usize)> {
+        let (height, width) = (height as i64, width as i64);
+        let fold = |v: i64, len: i64| {
+            if len == 1 {
+                return if v == 0 { Some(0) } else { None };
+            }
+            let v = v.rem_euclid(2 * (len - 1));
+            Some(if v < len { v } else { 2 * (len - 1) - v } as usize)
+        };
+        match self {
+            Boundary::Clip | Boundary::Constant(_) => {
+                if y >= 0 && y < height && x >= 0 && x < width {
+                    Some((y as usize, x as usize))
+                } else {
+                    None
+                }
+            }
+            Boundary::Torus => Some((y.rem_euclid(height) as usize, x.rem_euclid(width) as usize)),
+            Boundary::Reflect => Some((fold(y, height)?, fold(x, width)?)),
+        }
+    }
+
+    // Steps along (vert, hori) until a ray is back where it started, for boundaries that never
+    // let it leave.
+    fn ray_period(self, vert: i64, hori: i64, height: usize, width: usize) -> Option<i64> {
+        let (height, width) = match self {
+            Boundary::Clip | Boundary::Constant(_) => return None,
+            Boundary::Torus => (height as i64, width as i64),
+            // a ray along a side one cell long leaves straight away
+            Boundary::Reflect => (
+                (2 * (height as i64 - 1)).max(1),
+                (2 * (width as i64 - 1)).max(1),
+            ),
+        };
+        let along_y = if vert == 0 { 1 } else { height };
+        let along_x = if hori == 0 { 1 } else { width };
+        Some(along_y / gcd(&along_y, &along_x) * along_x)
+    }
+}
+
+// What to do with rows shorter or longer than the widest one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RaggedRows {
+    PadWithFloor,
+    Reject,
 }
 
-fn neighbour_coords_for(grid: &[Vec<GridStatus>]) -> Vec<Vec<Vec<(usize, usize)>>> {
-    (0..grid.len())
-        .map(|y| {
-            (0..grid[0].len())
-                .map(|x| {
-                    (((y as i32 - 1).max(0) as usize)..(y + 2).min(grid.len()))
-                        .flat_map(|row| {
-                            (((x as i32 - 1).max(0) as usize)..(x + 2).min(grid[0].len()))
-                                .flat_map(|col| {
-                                    if row == y && col == x {
-                                        vec![]
-                                    } else {
-                                        vec![(row, col)]
-                                    }
-                                })
-                                .collect::<Vec<_>>()
-                        })
-                        .collect::<Vec<_>>()
+pub struct GridFormat {
+    pub alphabet: HashMap<char, GridStatus>,
+    pub ragged_rows: RaggedRows,
+    // lines starting with this (leading whitespace aside) are skipped
+    pub comment_prefix: Option<String>,
+}
+
+impl Default for GridFormat {
+    fn default() -> GridFormat {
+        GridFormat {
+            alphabet: [
+                ('.', GridStatus::Floor),
+                ('L', GridStatus::Empty),
+                ('#', GridStatus::Taken),
+            ]
+            .iter()
+            .copied()
+            .collect(),
+            ragged_rows: RaggedRows::Reject,
+            comment_prefix: Some("//".to_string()),
+        }
+    }
+}
+
+impl GridFormat {
+    // Blank lines and comments are skipped and whitespace (\r included) around rows is ignored.
+    // Errors give 1-based line and column numbers.
+    pub fn parse(&self, input: &str) -> Result<Vec<Vec<GridStatus>>, String> {
+        let mut rows = vec![];
+        for (line_no, line) in input.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            let row = line.trim();
+            let is_comment = self
+                .comment_prefix
+                .as_ref()
+                .is_some_and(|prefix| row.starts_with(prefix.as_str()));
+            if row.is_empty() || is_comment {
+                continue;
+            }
+            let indent = line.chars().take_while(|ch| ch.is_whitespace()).count();
+            let cells = row
+                .chars()
+                .enumerate()
+                .map(|(col, ch)| {
+                    self.alphabet.get(&ch).copied().ok_or_else(|| {
+                        format!(
+                            "line {}, column {}: unexpected {:?}",
+                            line_no,
+                            indent + col + 1,
+                            ch
+                        )
+                    })
                 })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>()
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push((line_no, cells));
+        }
+        let width = rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0);
+        rows.into_iter()
+            .map(|(line_no, mut row)| match self.ragged_rows {
+                RaggedRows::Reject if row.len() != width => Err(format!(
+                    "line {}: row is {} cells wide, expected {}",
+                    line_no,
+                    row.len(),
+                    width
+                )),
+                _ => {
+                    row.resize(width, GridStatus::Floor);
+                    Ok(row)
+                }
+            })
+            .collect()
+    }
 }
 
-fn visible_neighbour_coords_for(grid: &[Vec<GridStatus>]) -> Vec<Vec<Vec<(usize, usize)>>> {
-    (0..grid.len())
-        .map(|y| {
-            (0..grid[0].len())
-                .map(|x| {
-                    let mut coords = vec![];
-                    for (vert, hori) in &[
-                        (-1_i32, -1_i32),
-                        (-1, 0),
-                        (-1, 1),
-                        (0, -1),
-                        (0, 1),
-                        (1, -1),
-                        (1, 0),
-                        (1, 1),
-                    ] {
-                        let (mut cur_y, mut cur_x) = (y as i32 + vert, x as i32 + hori);
-                        while cur_y >= 0
-                            && cur_y < (grid.len() as i32)
-                            && cur_x >= 0
-                            && cur_x < (grid[0].len() as i32)
+// Parses the usual `.L#` layout, panicking on anything it can't read.
+pub fn to_grid_status(input: &str) -> Vec<Vec<GridStatus>> {
+    GridFormat::default()
+        .parse(input)
+        .unwrap_or_else(|err| panic!("bad seat layout: {}", err))
+}
+
+// Neighbour lists for every cell of a grid, packed into two arrays. Cells are numbered row by row
+// (row * width + col), and the neighbours of cell i are cells[offsets[i]..offsets[i + 1]]. With a
+// constant boundary, border_counts[i] is how many of cell i's neighbours are off the grid (empty
+// when there are none anywhere).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NeighbourTable {
+    width: usize,
+    offsets: Vec<u32>,
+    cells: Vec<u32>,
+    border_state: GridStatus,
+    border_counts: Vec<u32>,
+}
+
+impl NeighbourTable {
+    fn build(
+        grid: &[Vec<GridStatus>],
+        boundary: Boundary,
+        mut neighbours_of: impl FnMut(usize, usize, &mut Vec<u32>) -> u32,
+    ) -> NeighbourTable {
+        let width = grid.first().map_or(0, Vec::len);
+        let mut offsets = Vec::with_capacity(grid.len() * width + 1);
+        let mut cells = vec![];
+        let mut border_counts = Vec::with_capacity(grid.len() * width);
+        offsets.push(0);
+        for y in 0..grid.len() {
+            for x in 0..width {
+                border_counts.push(neighbours_of(y, x, &mut cells));
+                offsets.push(cells.len() as u32);
+            }
+        }
+        if border_counts.iter().all(|count| *count == 0) {
+            border_counts = vec![];
+        }
+        NeighbourTable {
+            width,
+            offsets,
+            cells,
+            border_state: match boundary {
+                Boundary::Constant(state) => state,
+                _ => GridStatus::Floor,
+            },
+            border_counts,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn of(&self, cell: usize) -> &[u32] {
+        &self.cells[self.offsets[cell] as usize..self.offsets[cell + 1] as usize]
+    }
+
+    pub fn border_of(&self, cell: usize) -> (GridStatus, u32) {
+        (
+            self.border_state,
+            self.border_counts.get(cell).copied().unwrap_or(0),
+        )
+    }
+
+    pub fn coords_of(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.of(row * self.width + col)
+            .iter()
+            .map(|cell| (*cell as usize / self.width, *cell as usize % self.width))
+            .collect::<Vec<_>>()
+    }
+
+    // The table read backwards: for each cell, the cells that have it as a neighbour.
+    pub fn watchers(&self) -> NeighbourTable {
+        let mut offsets = vec![0; self.offsets.len()];
+        for cell in self.cells.iter() {
+            offsets[*cell as usize + 1] += 1;
+        }
+        for cell in 0..self.len() {
+            offsets[cell + 1] += offsets[cell];
+        }
+        let mut cells = vec![0; self.cells.len()];
+        let mut next_slot = offsets.clone();
+        for watcher in 0..self.len() {
+            for cell in self.of(watcher) {
+                cells[next_slot[*cell as usize] as usize] = watcher as u32;
+                next_slot[*cell as usize] += 1;
+            }
+        }
+        NeighbourTable {
+            width: self.width,
+            offsets,
+            cells,
+            border_state: self.border_state,
+            border_counts: vec![],
+        }
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        (self.offsets.capacity() + self.cells.capacity()) * std::mem::size_of::<u32>()
+            + self.border_counts.capacity()
+    }
+}
+
+const DIRECTIONS: [(i64, i64); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+pub fn neighbour_coords_for(grid: &[Vec<GridStatus>]) -> NeighbourTable {
+    neighbour_coords_with(grid, Boundary::Clip)
+}
+
+pub fn neighbour_coords_with(grid: &[Vec<GridStatus>], boundary: Boundary) -> NeighbourTable {
+    let (height, width) = (grid.len(), grid.first().map_or(0, Vec::len));
+    NeighbourTable::build(grid, boundary, |y, x, cells| {
+        let mut off_grid = 0;
+        for (vert, hori) in DIRECTIONS.iter() {
+            match boundary.resolve(y as i64 + vert, x as i64 + hori, height, width) {
+                Some((row, col)) => cells.push((row * width + col) as u32),
+                None => off_grid += 1,
+            }
+        }
+        match boundary {
+            Boundary::Constant(_) => off_grid,
+            _ => 0,
+        }
+    })
+}
+
+pub fn visible_neighbour_coords_for(grid: &[Vec<GridStatus>]) -> NeighbourTable {
+    visible_neighbour_coords_with(grid, Boundary::Clip)
+}
+
+// A ray that leaves the grid under a constant boundary sees the border, unless the border is floor.
+pub fn visible_neighbour_coords_with(
+    grid: &[Vec<GridStatus>],
+    boundary: Boundary,
+) -> NeighbourTable {
+    let (height, width) = (grid.len(), grid.first().map_or(0, Vec::len));
+    NeighbourTable::build(grid, boundary, |y, x, cells| {
+        let mut off_grid = 0;
+        for (vert, hori) in DIRECTIONS.iter() {
+            let period = boundary.ray_period(*vert, *hori, height, width);
+            let mut dist = 1;
+            while Some(dist) != period {
+                match boundary.resolve(
+                    y as i64 + vert * dist,
+                    x as i64 + hori * dist,
+                    height,
+                    width,
+                ) {
+                    Some(back) if back == (y, x) => break,
+                    Some((row, col)) if grid[row][col] != GridStatus::Floor => {
+                        cells.push((row * width + col) as u32);
+                        break;
+                    }
+                    Some(_) => dist += 1,
+                    None => {
+                        if let Boundary::Constant(GridStatus::Empty)
+                        | Boundary::Constant(GridStatus::Taken) = boundary
                         {
-                            if grid[cur_y as usize][cur_x as usize] != GridStatus::Floor {
-                                coords.push((cur_y as usize, cur_x as usize));
-                                break;
-                            }
-                            cur_y += vert;
-                            cur_x += hori;
+                            off_grid += 1;
                         }
+                        break;
                     }
-                    coords
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>()
+                }
+            }
+        }
+        off_grid
+    })
 }
 
 fn count_neighbours(
     grid: &[Vec<GridStatus>],
-    neighbour_coords: &[Vec<Vec<(usize, usize)>>],
+    neighbours: &NeighbourTable,
     x: usize,
     y: usize,
-) -> i32 {
-    neighbour_coords[y][x]
-        .iter()
-        .map(|(row, col)| {
-            match grid
-                .get(*row as usize)
-                .and_then(|row| row.get(*col as usize))
-            {
-                Some(GridStatus::Taken) => 1,
-                Some(GridStatus::Empty) | Some(GridStatus::Floor) | None => 0,
-            }
+) -> NeighbourCounts {
+    let cell = y * neighbours.width() + x;
+    let mut counts = NeighbourCounts::default();
+    for neighbour in neighbours.of(cell) {
+        let (row, col) = (
+            *neighbour as usize / neighbours.width(),
+            *neighbour as usize % neighbours.width(),
+        );
+        counts.add(grid[row][col], 1);
+    }
+    let (border_state, border_count) = neighbours.border_of(cell);
+    counts.add(border_state, border_count);
+    counts
+}
+
+// Whether the undecided cells (the Nones in `cells`) round `cell`, and `cell` itself if it's
+// undecided, can be filled in so that `rule` turns it into `target`, or leaves it as it is if
+// `target` is None. Floor stays floor.
+fn can_become(
+    rule: &Rule,
+    neighbours: &NeighbourTable,
+    cells: &[Option<GridStatus>],
+    cell: usize,
+    target: Option<GridStatus>,
+) -> bool {
+    let options: &[GridStatus] = match cells[cell] {
+        Some(GridStatus::Floor) => return target.is_none_or(|state| state == GridStatus::Floor),
+        Some(GridStatus::Taken) => &[GridStatus::Taken],
+        Some(GridStatus::Empty) => &[GridStatus::Empty],
+        None => &[GridStatus::Empty, GridStatus::Taken],
+    };
+    let mut known = NeighbourCounts::default();
+    let mut undecided = 0;
+    for neighbour in neighbours.of(cell) {
+        match cells[*neighbour as usize] {
+            Some(state) => known.add(state, 1),
+            None => undecided += 1,
+        }
+    }
+    let (border_state, border_count) = neighbours.border_of(cell);
+    known.add(border_state, border_count);
+    options.iter().any(|own| {
+        (0..=undecided).any(|taken| {
+            let counts = NeighbourCounts {
+                floor: known.floor,
+                empty: known.empty + undecided - taken,
+                taken: known.taken + taken,
+            };
+            rule.apply(*own, counts) == target.unwrap_or(*own)
         })
-        .sum()
+    })
 }
 
 fn pretty_print(grid: &[Vec<GridStatus>]) {
@@ -117,35 +433,7 @@ fn pretty_print(grid: &[Vec<GridStatus>]) {
     }
 }
 
-fn sim_one_round(
-    front: &[Vec<GridStatus>],
-    back: &mut [Vec<GridStatus>],
-    neighbour_coords: &[Vec<Vec<(usize, usize)>>],
-    threshold: i32,
-) {
-    for row in 0..front.len() {
-        for col in 0..front[0].len() {
-            let adjacent = count_neighbours(front, neighbour_coords, col, row);
-            if front[row][col] == GridStatus::Empty && adjacent == 0 {
-                back[row][col] = GridStatus::Taken;
-            } else if front[row][col] == GridStatus::Taken && adjacent >= threshold {
-                back[row][col] = GridStatus::Empty;
-            } else {
-                back[row][col] = front[row][col];
-            }
-        }
-    }
-}
-
-fn same_state(left: &[Vec<GridStatus>], right: &[Vec<GridStatus>]) -> bool {
-    left.len() == right.len()
-        && left
-            .iter()
-            .zip(right)
-            .all(|(l, r)| l.len() == r.len() && l.iter().zip(r).all(|(a, b)| a == b))
-}
-
-fn count_taken(grid: &[Vec<GridStatus>]) -> i32 {
+pub fn count_taken(grid: &[Vec<GridStatus>]) -> i32 {
     grid.iter()
         .map(|row| {
             row.iter()
@@ -159,39 +447,76 @@ fn count_taken(grid: &[Vec<GridStatus>]) -> i32 {
 }
 
 pub fn solve_p1(input: &str) -> i32 {
-    let mut front = &mut to_grid_status(input);
-    let mut back = &mut vec![vec![GridStatus::Floor; front[0].len()]; front.len()];
-    let neighbour_coords = neighbour_coords_for(&front);
-    // let mut round = 1;
-    while !same_state(front, back) {
-        sim_one_round(&front, back, &neighbour_coords, 4);
-        std::mem::swap(&mut front, &mut back);
-        // println!("\nresult of round {}: ---", round);
-        // round += 1;
-        // pretty_print(&front);
-    }
-    count_taken(front)
+    count_taken(
+        &Automaton::seating_p1()
+            .run_to_fixed_point(to_grid_status(input))
+            .expect("seating never settles"),
+    )
 }
 
 pub fn solve_p2(input: &str) -> i32 {
-    let mut front = &mut to_grid_status(input);
-    let mut back = &mut vec![vec![GridStatus::Floor; front[0].len()]; front.len()];
-    let neighbour_coords = visible_neighbour_coords_for(&front);
-    // let mut round = 1;
-    while !same_state(front, back) {
-        sim_one_round(&front, back, &neighbour_coords, 5);
-        std::mem::swap(&mut front, &mut back);
-        // println!("\nresult of round {}: ---", round);
-        // round += 1;
-        // pretty_print(&front);
-    }
-    count_taken(front)
+    count_taken(
+        &Automaton::seating_p2()
+            .run_to_fixed_point(to_grid_status(input))
+            .expect("seating never settles"),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // Deterministic seat map with roughly one floor tile in eight, for tests and benchmarks that
+    // want something bigger than the puzzle input.
+    // Every layout with the same floor as `floor_plan`, each seat empty or taken.
+    pub(crate) fn every_seating(
+        floor_plan: &[Vec<GridStatus>],
+    ) -> impl Iterator<Item = Vec<Vec<GridStatus>>> + '_ {
+        let seats = floor_plan
+            .iter()
+            .flatten()
+            .filter(|cell| **cell != GridStatus::Floor)
+            .count();
+        (0..1u32 << seats).map(move |mask| {
+            let mut seat = 0;
+            let mut grid = floor_plan.to_vec();
+            for cell in grid.iter_mut().flatten() {
+                if *cell != GridStatus::Floor {
+                    *cell = if mask >> seat & 1 == 1 {
+                        GridStatus::Taken
+                    } else {
+                        GridStatus::Empty
+                    };
+                    seat += 1;
+                }
+            }
+            grid
+        })
+    }
+
+    pub(crate) fn generated_seat_map(width: usize, height: usize, seed: u64) -> String {
+        let mut state = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        if state >> 61 == 0 {
+                            '.'
+                        } else {
+                            'L'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     static SMALL_STATE: &str = "L.L
 L##
 .L#";
@@ -220,11 +545,54 @@ L.LLLLL.LL";
         );
     }
 
+    #[test]
+    fn parse_grid_formats() {
+        use GridStatus::*;
+        let expected = to_grid_status(SMALL_STATE);
+        assert_eq!(
+            expected,
+            to_grid_status("// seats\r\nL.L\r\n  L## \r\n\r\n.L#\r\n")
+        );
+
+        let format = GridFormat {
+            alphabet: [('_', Floor), ('o', Empty), ('x', Taken)]
+                .iter()
+                .copied()
+                .collect(),
+            comment_prefix: Some(";".to_string()),
+            ..GridFormat::default()
+        };
+        assert_eq!(Ok(expected), format.parse("; seats\no_o\noxx\n_ox"));
+        assert_eq!(
+            Err("line 2, column 4: unexpected 'L'".to_string()),
+            format.parse("o_o\n  oL\n")
+        );
+
+        let ragged = "L.L\nL\n.L#L";
+        assert_eq!(
+            Err("line 1: row is 3 cells wide, expected 4".to_string()),
+            GridFormat::default().parse(ragged)
+        );
+        let format = GridFormat {
+            ragged_rows: RaggedRows::PadWithFloor,
+            ..GridFormat::default()
+        };
+        assert_eq!(
+            Ok(vec![
+                vec![Empty, Floor, Empty, Floor],
+                vec![Empty, Floor, Floor, Floor],
+                vec![Floor, Empty, Taken, Empty]
+            ]),
+            format.parse(ragged)
+        );
+        assert_eq!(Ok(vec![]), format.parse("\n// nothing here\n"));
+    }
+
     #[test]
     fn neighbour_coords() {
         assert_eq!(
             vec![(0, 1), (1, 0), (1, 1)],
-            neighbour_coords_for(&to_grid_status(SMALL_STATE))[0][0]
+            neighbour_coords_for(&to_grid_status(SMALL_STATE)).coords_of(0, 0)
         );
         assert_eq!(
             vec![
@@ -237,7 +605,7 @@ L.LLLLL.LL";
                 (2, 1),
                 (2, 2)
             ],
-            neighbour_coords_for(&to_grid_status(SMALL_STATE))[1][1]
+            neighbour_coords_for(&to_grid_status(SMALL_STATE)).coords_of(1, 1)
         );
     }
 
@@ -245,8 +613,152 @@ L.LLLLL.LL";
     fn neighbour_counts() {
         let grid_status = to_grid_status(SMALL_STATE);
         let neighbour_coords = neighbour_coords_for(&grid_status);
-        assert_eq!(3, count_neighbours(&grid_status, &neighbour_coords, 1, 2));
-        assert_eq!(2, count_neighbours(&grid_status, &neighbour_coords, 1, 0));
+        assert_eq!(
+            3,
+            count_neighbours(&grid_status, &neighbour_coords, 1, 2).taken
+        );
+        assert_eq!(
+            2,
+            count_neighbours(&grid_status, &neighbour_coords, 1, 0).taken
+        );
+    }
+
+    #[test]
+    fn neighbour_table_memory() {
+        use std::mem::size_of;
+        let grid = to_grid_status(&generated_seat_map(500, 500, 11));
+        for table in [
+            neighbour_coords_for(&grid),
+            visible_neighbour_coords_for(&grid),
+        ]
+        .iter()
+        {
+            let entries = (0..table.len())
+                .map(|cell| table.of(cell).len())
+                .sum::<usize>();
+            // the same lists as Vec<Vec<Vec<(usize, usize)>>>, not counting spare capacity
+            let nested = size_of::<Vec<()>>() * (1 + grid.len() + table.len())
+                + size_of::<(usize, usize)>() * entries;
+            println!(
+                "{} neighbours: {} bytes nested, {} bytes packed, {} saved",
+                entries,
+                nested,
+                table.heap_bytes(),
+                nested - table.heap_bytes()
+            );
+            assert!(table.heap_bytes() * 3 < nested);
+        }
+    }
+
+    #[test]
+    fn watchers_reverse_the_table() {
+        let grid = to_grid_status(&generated_seat_map(17, 9, 4));
+        let table = visible_neighbour_coords_for(&grid);
+        let watchers = table.watchers();
+        for cell in 0..table.len() {
+            for neighbour in table.of(cell) {
+                assert!(watchers.of(*neighbour as usize).contains(&(cell as u32)));
+            }
+        }
+        let round_trip = watchers.watchers();
+        for cell in 0..table.len() {
+            let mut neighbours = table.of(cell).to_vec();
+            neighbours.sort_unstable();
+            assert_eq!(&neighbours[..], round_trip.of(cell));
+        }
+    }
+
+    #[test]
+    fn boundary_neighbours() {
+        let grid = to_grid_status(SMALL_STATE);
+        assert_eq!(
+            vec![
+                (2, 2),
+                (2, 0),
+                (2, 1),
+                (0, 2),
+                (0, 1),
+                (1, 2),
+                (1, 0),
+                (1, 1)
+            ],
+            neighbour_coords_with(&grid, Boundary::Torus).coords_of(0, 0)
+        );
+        assert_eq!(
+            vec![
+                (1, 1),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (0, 1),
+                (1, 1),
+                (1, 0),
+                (1, 1)
+            ],
+            neighbour_coords_with(&grid, Boundary::Reflect).coords_of(0, 0)
+        );
+        let mirrored = neighbour_coords_with(&grid, Boundary::Reflect);
+        for cell in 0..mirrored.len() {
+            assert_eq!(8, mirrored.of(cell).len());
+            assert!(!mirrored.of(cell).contains(&(cell as u32)));
+        }
+        let single = to_grid_status("L");
+        assert!(neighbour_coords_with(&single, Boundary::Reflect)
+            .of(0)
+            .is_empty());
+        let constant = neighbour_coords_with(&grid, Boundary::Constant(GridStatus::Taken));
+        assert_eq!(vec![(0, 1), (1, 0), (1, 1)], constant.coords_of(0, 0));
+        assert_eq!(
+            NeighbourCounts {
+                floor: 1,
+                empty: 1,
+                taken: 6
+            },
+            count_neighbours(&grid, &constant, 0, 0)
+        );
+        assert_eq!((GridStatus::Taken, 0), constant.border_of(4));
+    }
+
+    #[test]
+    fn boundary_lines_of_sight() {
+        let grid = to_grid_status("#..L");
+        assert_eq!(
+            vec![(0, 0)],
+            visible_neighbour_coords_with(&grid, Boundary::Clip).coords_of(0, 3)
+        );
+        // one row high, so diagonals wrap straight back onto the same row
+        assert_eq!(
+            vec![(0, 0); 6],
+            visible_neighbour_coords_with(&grid, Boundary::Torus).coords_of(0, 3)
+        );
+        let constant = visible_neighbour_coords_with(&grid, Boundary::Constant(GridStatus::Taken));
+        assert_eq!(vec![(0, 0)], constant.coords_of(0, 3));
+        assert_eq!((GridStatus::Taken, 7), constant.border_of(3));
+        let floor = visible_neighbour_coords_with(&grid, Boundary::Constant(GridStatus::Floor));
+        assert_eq!((GridStatus::Floor, 0), floor.border_of(3));
+        // only along the row, and back off the east edge
+        assert_eq!(
+            vec![(0, 0), (0, 0)],
+            visible_neighbour_coords_with(&grid, Boundary::Reflect).coords_of(0, 3)
+        );
+        // both rays bounce straight back to the seat they started from
+        let lonely = visible_neighbour_coords_with(&to_grid_status(".L.."), Boundary::Reflect);
+        assert!(lonely.coords_of(0, 1).is_empty());
+
+        // an all-floor torus must not send rays round forever
+        let empty = visible_neighbour_coords_with(&to_grid_status("...\n..."), Boundary::Torus);
+        assert!((0..empty.len()).all(|cell| empty.of(cell).is_empty()));
+    }
+
+    #[test]
+    fn glider_round_a_torus() {
+        let life = Automaton::new(
+            automaton::Rule::parse("B3/S23").unwrap(),
+            |grid: &[Vec<GridStatus>]| neighbour_coords_with(grid, Boundary::Torus),
+        );
+        let glider = to_grid_status("L#LLLL\nLL#LLL\n###LLL\nLLLLLL\nLLLLLL\nLLLLLL");
+        let cycle = life.find_cycle(glider);
+        assert_eq!((0, 24), (cycle.transient, cycle.period));
     }
 
     #[test]
@@ -280,7 +792,7 @@ L.LLLLL.LL";
                 (8, 3),
                 (5, 4)
             ],
-            visible_neighbours[4][3]
+            visible_neighbours.coords_of(4, 3)
         );
     }
 
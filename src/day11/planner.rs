@@ -0,0 +1,307 @@
+use super::automaton::{Automaton, Rule};
+use super::{can_become, GridStatus, NeighbourCounts, NeighbourTable};
+use std::collections::HashSet;
+
+// Grids with at most this many seats are searched exhaustively by `plan_seats`.
+pub const EXACT_SEATS: usize = 30;
+
+// Seats in a flat array along with who looks at whom, as given by the automaton's neighbourhood.
+struct Plan<'a> {
+    rule: &'a Rule,
+    cells: Vec<GridStatus>,
+    width: usize,
+    neighbours: NeighbourTable,
+    watchers: NeighbourTable,
+    seats: Vec<usize>,
+}
+
+impl<'a> Plan<'a> {
+    // Every seat of `floor_plan` starts out empty.
+    fn new(automaton: &'a Automaton, floor_plan: &[Vec<GridStatus>]) -> Plan<'a> {
+        let neighbours = automaton.neighbour_coords(floor_plan);
+        let cells = floor_plan
+            .iter()
+            .flatten()
+            .map(|cell| match cell {
+                GridStatus::Floor => GridStatus::Floor,
+                _ => GridStatus::Empty,
+            })
+            .collect::<Vec<_>>();
+        Plan {
+            rule: automaton.rule(),
+            width: floor_plan.first().map_or(0, Vec::len).max(1),
+            watchers: neighbours.watchers(),
+            neighbours,
+            seats: (0..cells.len())
+                .filter(|cell| cells[*cell] != GridStatus::Floor)
+                .collect(),
+            cells,
+        }
+    }
+
+    fn grid(&self, cells: &[GridStatus]) -> Vec<Vec<GridStatus>> {
+        cells.chunks(self.width).map(|row| row.to_vec()).collect()
+    }
+
+    // Whether the undecided seats around `cell` (and `cell` itself, if undecided) can still be
+    // filled in so that the rule leaves it as it is.
+    fn can_stay(&self, cells: &[Option<GridStatus>], cell: usize) -> bool {
+        can_become(self.rule, &self.neighbours, cells, cell, None)
+    }
+
+    fn next_state(&self, cells: &[GridStatus], cell: usize) -> GridStatus {
+        let mut counts = NeighbourCounts::default();
+        for neighbour in self.neighbours.of(cell) {
+            counts.add(cells[*neighbour as usize], 1);
+        }
+        let (border_state, border_count) = self.neighbours.border_of(cell);
+        counts.add(border_state, border_count);
+        self.rule.apply(cells[cell], counts)
+    }
+}
+
+// Branch and bound over seats in reading order, trying taken before empty. A branch is dropped as
+// soon as some seat can no longer stay put, or when filling every undecided seat still wouldn't
+// beat the best layout so far.
+struct Exact<'a, 'b> {
+    plan: &'b Plan<'a>,
+    cells: Vec<Option<GridStatus>>,
+    taken: usize,
+    best: Option<(usize, Vec<GridStatus>)>,
+}
+
+impl<'a, 'b> Exact<'a, 'b> {
+    fn search(&mut self, depth: usize) {
+        let seats = &self.plan.seats;
+        if matches!(&self.best, Some((best, _)) if self.taken + seats.len() - depth <= *best) {
+            return;
+        }
+        if depth == seats.len() {
+            let cells = self.cells.iter().map(|cell| cell.unwrap()).collect();
+            self.best = Some((self.taken, cells));
+            return;
+        }
+        let seat = seats[depth];
+        for state in [GridStatus::Taken, GridStatus::Empty].iter() {
+            self.cells[seat] = Some(*state);
+            let plan = self.plan;
+            if plan.can_stay(&self.cells, seat)
+                && plan
+                    .watchers
+                    .of(seat)
+                    .iter()
+                    .all(|watcher| plan.can_stay(&self.cells, *watcher as usize))
+            {
+                self.taken += (*state == GridStatus::Taken) as usize;
+                self.search(depth + 1);
+                self.taken -= (*state == GridStatus::Taken) as usize;
+            }
+        }
+        self.cells[seat] = None;
+    }
+}
+
+// The layout with the most taken seats that `automaton` leaves exactly as it is, or None if no
+// layout is left alone. The seats of `floor_plan` can be empty or taken, it makes no difference.
+// Exponential in the number of seats.
+pub fn plan_exact(
+    automaton: &Automaton,
+    floor_plan: &[Vec<GridStatus>],
+) -> Option<Vec<Vec<GridStatus>>> {
+    let plan = Plan::new(automaton, floor_plan);
+    let mut exact = Exact {
+        cells: plan
+            .cells
+            .iter()
+            .map(|cell| Some(*cell).filter(|cell| *cell == GridStatus::Floor))
+            .collect(),
+        plan: &plan,
+        taken: 0,
+        best: None,
+    };
+    exact.search(0);
+    exact.best.map(|(_, cells)| plan.grid(&cells))
+}
+
+// Updates cells one at a time, in place, starting from `queue`, until none of them would change.
+// Unlike whole rounds this can't flip-flop between two layouts on the seating rules, but a custom
+// rule might still never settle, so it gives up after `max_updates`. Every change goes in `undo`.
+fn settle(
+    plan: &Plan,
+    cells: &mut [GridStatus],
+    queue: &mut Vec<usize>,
+    undo: &mut Vec<(usize, GridStatus)>,
+    max_updates: usize,
+) -> bool {
+    let mut updates = 0;
+    while let Some(cell) = queue.pop() {
+        let next = plan.next_state(cells, cell);
+        if next == cells[cell] {
+            continue;
+        }
+        if updates == max_updates {
+            queue.clear();
+            return false;
+        }
+        updates += 1;
+        undo.push((cell, cells[cell]));
+        cells[cell] = next;
+        queue.push(cell);
+        queue.extend(plan.watchers.of(cell).iter().map(|w| *w as usize));
+    }
+    true
+}
+
+// How many more seats are taken now than before the changes in `undo`.
+fn taken_change(cells: &[GridStatus], undo: &[(usize, GridStatus)]) -> isize {
+    let mut seen = HashSet::new();
+    undo.iter()
+        .filter(|(cell, _)| seen.insert(*cell))
+        .map(|(cell, before)| {
+            (cells[*cell] == GridStatus::Taken) as isize - (*before == GridStatus::Taken) as isize
+        })
+        .sum()
+}
+
+// Starts from all seats empty, lets the rule settle things, then keeps kicking a random seat
+// (taking it, and sometimes emptying everything around it), letting things settle again and
+// keeping the result unless it has fewer taken seats. None if the rule never settles at all.
+pub fn plan_local_search(
+    automaton: &Automaton,
+    floor_plan: &[Vec<GridStatus>],
+    iterations: usize,
+    seed: u64,
+) -> Option<Vec<Vec<GridStatus>>> {
+    let plan = Plan::new(automaton, floor_plan);
+    let max_updates = 16 * plan.cells.len() + 64;
+    let mut cells = plan.cells.clone();
+    let (mut queue, mut undo) = ((0..cells.len()).rev().collect(), vec![]);
+    if !settle(&plan, &mut cells, &mut queue, &mut undo, max_updates) {
+        return None;
+    }
+    if plan.seats.is_empty() {
+        return Some(plan.grid(&cells));
+    }
+    let mut state = seed;
+    for _ in 0..iterations {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let seat = plan.seats[(state >> 33) as usize % plan.seats.len()];
+        undo.clear();
+        let mut kick = vec![(seat, GridStatus::Taken)];
+        if state >> 32 & 1 == 1 {
+            kick.extend(
+                plan.neighbours
+                    .of(seat)
+                    .iter()
+                    .map(|n| (*n as usize, GridStatus::Empty)),
+            );
+        }
+        for (cell, next) in kick {
+            if cells[cell] != GridStatus::Floor && cells[cell] != next {
+                undo.push((cell, cells[cell]));
+                cells[cell] = next;
+                queue.push(cell);
+                queue.extend(plan.watchers.of(cell).iter().map(|w| *w as usize));
+            }
+        }
+        let settled = settle(&plan, &mut cells, &mut queue, &mut undo, max_updates);
+        if !settled || taken_change(&cells, &undo) < 0 {
+            for (cell, before) in undo.drain(..).rev() {
+                cells[cell] = before;
+            }
+        }
+    }
+    Some(plan.grid(&cells))
+}
+
+// Exact up to `EXACT_SEATS` seats, local search past that.
+pub fn plan_seats(
+    automaton: &Automaton,
+    floor_plan: &[Vec<GridStatus>],
+) -> Option<Vec<Vec<GridStatus>>> {
+    let seats = floor_plan
+        .iter()
+        .flatten()
+        .filter(|cell| **cell != GridStatus::Floor)
+        .count();
+    if seats <= EXACT_SEATS {
+        plan_exact(automaton, floor_plan)
+    } else {
+        plan_local_search(automaton, floor_plan, 50 * seats, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day11::tests::{every_seating, generated_seat_map};
+    use crate::day11::{count_taken, to_grid_status};
+
+    fn is_fixed_point(automaton: &Automaton, grid: &[Vec<GridStatus>]) -> bool {
+        let mut next = grid.to_vec();
+        !automaton.step(grid, &mut next, &automaton.neighbour_coords(grid))
+    }
+
+    // The most taken seats over every layout that stays put, by trying them all.
+    fn brute_force(automaton: &Automaton, floor_plan: &[Vec<GridStatus>]) -> Option<i32> {
+        every_seating(floor_plan)
+            .filter(|grid| is_fixed_point(automaton, grid))
+            .map(|grid| count_taken(&grid))
+            .max()
+    }
+
+    #[test]
+    fn exact_plans_are_best() {
+        for seed in 0..8 {
+            let floor_plan = to_grid_status(&generated_seat_map(4, 4, seed));
+            for automaton in [Automaton::seating_p1(), Automaton::seating_p2()].iter() {
+                let best = plan_exact(automaton, &floor_plan).unwrap();
+                assert!(is_fixed_point(automaton, &best));
+                assert_eq!(
+                    brute_force(automaton, &floor_plan),
+                    Some(count_taken(&best))
+                );
+                let floor = |grid: &[Vec<GridStatus>]| {
+                    grid.iter()
+                        .flatten()
+                        .map(|cell| *cell == GridStatus::Floor)
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(floor(&floor_plan), floor(&best));
+            }
+        }
+        // a lone seat next to nothing always gets taken, so it can't stay empty
+        let p1 = Automaton::seating_p1();
+        assert_eq!(
+            Some(to_grid_status("#.#")),
+            plan_exact(&p1, &to_grid_status("L.L"))
+        );
+    }
+
+    #[test]
+    fn local_search_stays_valid() {
+        for seed in 0..4 {
+            let floor_plan = to_grid_status(&generated_seat_map(6, 5, seed));
+            for automaton in [Automaton::seating_p1(), Automaton::seating_p2()].iter() {
+                let found = plan_local_search(automaton, &floor_plan, 2000, seed).unwrap();
+                assert!(is_fixed_point(automaton, &found));
+                let best = plan_exact(automaton, &floor_plan).unwrap();
+                assert!(count_taken(&found) <= count_taken(&best));
+                assert!(count_taken(&found) * 10 >= count_taken(&best) * 9);
+            }
+        }
+    }
+
+    #[test]
+    fn beats_the_puzzle_seating() {
+        let input =
+            std::fs::read_to_string("src/day11/input.in").expect("failed to read day11 input");
+        let floor_plan = to_grid_status(&input);
+        let p1 = Automaton::seating_p1();
+        let planned = plan_seats(&p1, &floor_plan).unwrap();
+        assert!(is_fixed_point(&p1, &planned));
+        assert!(count_taken(&planned) >= 2261);
+    }
+}
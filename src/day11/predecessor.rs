@@ -0,0 +1,176 @@
+use super::automaton::{Automaton, Rule};
+use super::{can_become, GridStatus, NeighbourTable};
+
+// Backtracking over seats in reading order. Every time a seat is given a state, it and every cell
+// watching it are checked: with the neighbours still undecided free to go either way, there has
+// to be some way of filling them in that leads to the target cell. Floor is taken to stay floor,
+// which is all life-like rules can do, so only seats are ever guessed.
+struct Search<'a> {
+    rule: &'a Rule,
+    target: Vec<GridStatus>,
+    neighbours: NeighbourTable,
+    watchers: NeighbourTable,
+    seats: Vec<usize>,
+    cells: Vec<Option<GridStatus>>,
+    width: usize,
+    limit: usize,
+    found: Vec<Vec<Vec<GridStatus>>>,
+}
+
+impl<'a> Search<'a> {
+    fn new(automaton: &'a Automaton, target: &[Vec<GridStatus>], limit: usize) -> Search<'a> {
+        let neighbours = automaton.neighbour_coords(target);
+        let width = target.first().map_or(0, Vec::len).max(1);
+        let target = target.iter().flatten().copied().collect::<Vec<_>>();
+        Search {
+            rule: automaton.rule(),
+            watchers: neighbours.watchers(),
+            neighbours,
+            seats: (0..target.len())
+                .filter(|cell| target[*cell] != GridStatus::Floor)
+                .collect(),
+            cells: target
+                .iter()
+                .map(|cell| Some(*cell).filter(|cell| *cell == GridStatus::Floor))
+                .collect(),
+            width,
+            target,
+            limit,
+            found: vec![],
+        }
+    }
+
+    fn feasible(&self, cell: usize) -> bool {
+        let target = Some(self.target[cell]);
+        can_become(self.rule, &self.neighbours, &self.cells, cell, target)
+    }
+
+    fn search(&mut self, depth: usize) {
+        if self.found.len() >= self.limit {
+            return;
+        }
+        if depth == self.seats.len() {
+            let cells = self.cells.iter().map(|cell| cell.unwrap());
+            let grid = cells.collect::<Vec<_>>();
+            self.found
+                .push(grid.chunks(self.width).map(|row| row.to_vec()).collect());
+            return;
+        }
+        let seat = self.seats[depth];
+        for state in [GridStatus::Empty, GridStatus::Taken].iter() {
+            self.cells[seat] = Some(*state);
+            if self.feasible(seat)
+                && self
+                    .watchers
+                    .of(seat)
+                    .iter()
+                    .all(|watcher| self.feasible(*watcher as usize))
+            {
+                self.search(depth + 1);
+            }
+        }
+        self.cells[seat] = None;
+    }
+}
+
+// Up to `limit` layouts that `automaton` turns into `target` in one round. The search is
+// exponential in the number of seats, so this is for small grids.
+pub fn find_predecessors(
+    automaton: &Automaton,
+    target: &[Vec<GridStatus>],
+    limit: usize,
+) -> Vec<Vec<Vec<GridStatus>>> {
+    let mut search = Search::new(automaton, target, limit);
+    search.search(0);
+    search.found
+}
+
+pub fn find_predecessor(
+    automaton: &Automaton,
+    target: &[Vec<GridStatus>],
+) -> Option<Vec<Vec<GridStatus>>> {
+    find_predecessors(automaton, target, 1).pop()
+}
+
+// A layout nothing leads to.
+pub fn is_garden_of_eden(automaton: &Automaton, target: &[Vec<GridStatus>]) -> bool {
+    find_predecessor(automaton, target).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day11::tests::{every_seating, generated_seat_map};
+    use crate::day11::{neighbour_coords_with, to_grid_status, Boundary};
+
+    fn step(automaton: &Automaton, grid: &[Vec<GridStatus>]) -> Vec<Vec<GridStatus>> {
+        let mut next = grid.to_vec();
+        automaton.step(grid, &mut next, &automaton.neighbour_coords(grid));
+        next
+    }
+
+    // Every layout with the same floor as `target` that leads to it, found by trying them all.
+    fn brute_force(automaton: &Automaton, target: &[Vec<GridStatus>]) -> Vec<Vec<Vec<GridStatus>>> {
+        every_seating(target)
+            .filter(|grid| step(automaton, grid) == target)
+            .collect()
+    }
+
+    #[test]
+    fn predecessors_lead_to_the_target() {
+        let automata = [
+            Automaton::seating_p1(),
+            Automaton::seating_p2(),
+            Automaton::new(
+                Rule::parse("B3/S23").unwrap(),
+                |grid: &[Vec<GridStatus>]| neighbour_coords_with(grid, Boundary::Torus),
+            ),
+        ];
+        for (seed, (width, height)) in [(1, 1), (2, 3), (4, 3), (5, 5), (7, 6)].iter().enumerate() {
+            let grid = to_grid_status(&generated_seat_map(*width, *height, seed as u64));
+            for automaton in automata.iter() {
+                let target = step(automaton, &step(automaton, &grid));
+                let predecessor = find_predecessor(automaton, &target).unwrap();
+                assert_eq!(target, step(automaton, &predecessor));
+                assert!(!is_garden_of_eden(automaton, &target));
+            }
+        }
+    }
+
+    #[test]
+    fn finds_every_predecessor() {
+        let automata = [Automaton::seating_p1(), Automaton::seating_p2()];
+        for seed in 0..6 {
+            let grid = to_grid_status(&generated_seat_map(4, 3, seed));
+            for automaton in automata.iter() {
+                for target in [step(automaton, &grid), grid.clone()].iter() {
+                    let mut found = find_predecessors(automaton, target, usize::MAX);
+                    found.sort_by_key(|grid| format!("{:?}", grid));
+                    let mut expected = brute_force(automaton, target);
+                    expected.sort_by_key(|grid| format!("{:?}", grid));
+                    assert_eq!(expected, found);
+                    assert_eq!(
+                        expected.len().min(2),
+                        find_predecessors(automaton, target, 2).len()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gardens_of_eden() {
+        // an empty seat with nobody around always gets taken, and a taken one keeps its place
+        let p1 = Automaton::seating_p1();
+        assert!(is_garden_of_eden(&p1, &to_grid_status("L")));
+        assert!(is_garden_of_eden(&p1, &to_grid_status("LL")));
+        assert!(is_garden_of_eden(
+            &Automaton::seating_p2(),
+            &to_grid_status("L.L")
+        ));
+        assert_eq!(
+            Some(to_grid_status("L")),
+            find_predecessor(&p1, &to_grid_status("#"))
+        );
+    }
+}
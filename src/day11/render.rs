@@ -0,0 +1,449 @@
+use super::automaton::Automaton;
+use super::GridStatus;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub floor: [u8; 3],
+    pub empty: [u8; 3],
+    pub taken: [u8; 3],
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            floor: [32, 32, 32],
+            empty: [64, 160, 64],
+            taken: [200, 48, 48],
+        }
+    }
+}
+
+impl Palette {
+    fn colour(&self, cell: GridStatus) -> [u8; 3] {
+        match cell {
+            GridStatus::Floor => self.floor,
+            GridStatus::Empty => self.empty,
+            GridStatus::Taken => self.taken,
+        }
+    }
+}
+
+// Every generation from `grid` on, stopping after the first one that changes nothing or after
+// `max_generations` steps, whichever comes first.
+pub fn generations(
+    automaton: &Automaton,
+    grid: Vec<Vec<GridStatus>>,
+    max_generations: usize,
+) -> Vec<Vec<Vec<GridStatus>>> {
+    let neighbour_coords = automaton.neighbour_coords(&grid);
+    let mut frames = vec![grid];
+    while frames.len() <= max_generations {
+        let front = &frames[frames.len() - 1];
+        let mut back = front.clone();
+        if !automaton.step(front, &mut back, &neighbour_coords) {
+            break;
+        }
+        frames.push(back);
+    }
+    frames
+}
+
+// Cells come out as `scale` x `scale` squares.
+fn pixels<T: Copy>(
+    grid: &[Vec<GridStatus>],
+    scale: usize,
+    pixel: impl Fn(GridStatus) -> T,
+) -> Vec<T> {
+    grid.iter()
+        .flat_map(|row| {
+            let line = row
+                .iter()
+                .flat_map(|cell| std::iter::repeat_n(pixel(*cell), scale))
+                .collect::<Vec<_>>();
+            std::iter::repeat_n(line, scale).flatten()
+        })
+        .collect()
+}
+
+// A binary (P6) PPM image of one generation.
+pub fn to_ppm(grid: &[Vec<GridStatus>], palette: &Palette, scale: usize) -> Vec<u8> {
+    let width = grid.first().map_or(0, Vec::len) * scale;
+    let mut ppm = format!("P6\n{} {}\n255\n", width, grid.len() * scale).into_bytes();
+    ppm.extend(pixels(grid, scale, |cell| palette.colour(cell)).concat());
+    ppm
+}
+
+// Writes frame_00000.ppm, frame_00001.ppm, ... into `dir`, which has to exist.
+pub fn write_ppm_frames(
+    frames: &[Vec<Vec<GridStatus>>],
+    dir: &Path,
+    palette: &Palette,
+    scale: usize,
+) -> io::Result<()> {
+    for (i, grid) in frames.iter().enumerate() {
+        fs::write(
+            dir.join(format!("frame_{:05}.ppm", i)),
+            to_ppm(grid, palette, scale),
+        )?;
+    }
+    Ok(())
+}
+
+// Variable-width LZW codes packed least significant bit first, as GIF wants them, for images
+// with a 4 colour table (so 2 bit minimum code size).
+struct LzwWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+    size: u32,
+    next_code: u16,
+}
+
+const LZW_CLEAR: u16 = 4;
+const LZW_END: u16 = 5;
+const LZW_MAX_CODE: u16 = 4095;
+
+impl LzwWriter {
+    fn emit(&mut self, code: u16) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += self.size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+        if self.next_code >= 1 << self.size && self.size < 12 {
+            self.size += 1;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.size = 3;
+        self.next_code = LZW_END + 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    let mut writer = LzwWriter {
+        bytes: vec![],
+        buffer: 0,
+        bits: 0,
+        size: 3,
+        next_code: LZW_END + 1,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    writer.emit(LZW_CLEAR);
+    let mut current = None;
+    for index in indices.iter().copied() {
+        let prefix = match current {
+            None => {
+                current = Some(index as u16);
+                continue;
+            }
+            Some(prefix) => prefix,
+        };
+        if let Some(code) = table.get(&(prefix, index)) {
+            current = Some(*code);
+            continue;
+        }
+        writer.emit(prefix);
+        if writer.next_code >= LZW_MAX_CODE {
+            writer.emit(LZW_CLEAR);
+            writer.reset();
+            table.clear();
+        } else {
+            table.insert((prefix, index), writer.next_code);
+            writer.next_code += 1;
+        }
+        current = Some(index as u16);
+    }
+    if let Some(prefix) = current {
+        writer.emit(prefix);
+    }
+    writer.emit(LZW_END);
+    writer.finish()
+}
+
+// An animated GIF of `frames` that loops forever. GIF delays are in hundredths of a second, and
+// images can't be more than 65535 pixels across or down.
+pub fn to_gif(
+    frames: &[Vec<Vec<GridStatus>>],
+    palette: &Palette,
+    scale: usize,
+    delay: Duration,
+) -> io::Result<Vec<u8>> {
+    let dimension = |cells: usize| {
+        cells
+            .checked_mul(scale)
+            .and_then(|pixels| u16::try_from(pixels).ok())
+            .map(u16::to_le_bytes)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} cells at scale {} is too large for a gif", cells, scale),
+                )
+            })
+    };
+    let first = frames.first().map_or(&[][..], |grid| &grid[..]);
+    let (width, height) = (
+        dimension(first.first().map_or(0, Vec::len))?,
+        dimension(first.len())?,
+    );
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(&width);
+    gif.extend(&height);
+    // global colour table of 2^(1 + 1) entries
+    gif.extend(&[0x91, 0, 0]);
+    for colour in [palette.floor, palette.empty, palette.taken, [0; 3]].iter() {
+        gif.extend(colour);
+    }
+    gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+    let centiseconds = u16::try_from(delay.as_millis() / 10).unwrap_or(u16::MAX);
+    for grid in frames.iter() {
+        gif.extend(&[0x21, 0xf9, 4, 0]);
+        gif.extend(&centiseconds.to_le_bytes());
+        gif.extend(&[0, 0, 0x2c, 0, 0, 0, 0]);
+        gif.extend(&width);
+        gif.extend(&height);
+        gif.extend(&[0, 2]);
+        for block in lzw_encode(&pixels(grid, scale, |cell| cell as u8)).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3b);
+    Ok(gif)
+}
+
+// Plays generations in a terminal that understands 24-bit ANSI colours, every cell drawn as two
+// coloured spaces so it comes out roughly square.
+pub struct TerminalAnimation {
+    pub palette: Palette,
+    pub frame_delay: Duration,
+}
+
+impl Default for TerminalAnimation {
+    fn default() -> TerminalAnimation {
+        TerminalAnimation {
+            palette: Palette::default(),
+            frame_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl TerminalAnimation {
+    // Moves the cursor home first, so frames written one after another draw over each other.
+    pub fn frame(&self, grid: &[Vec<GridStatus>]) -> String {
+        let mut out = String::from("\x1b[H");
+        for row in grid.iter() {
+            let mut last = None;
+            for cell in row.iter() {
+                if last != Some(*cell) {
+                    let [r, g, b] = self.palette.colour(*cell);
+                    out.push_str(&format!("\x1b[48;2;{};{};{}m", r, g, b));
+                    last = Some(*cell);
+                }
+                out.push_str("  ");
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
+    }
+
+    pub fn play(&self, frames: &[Vec<Vec<GridStatus>>], out: &mut impl Write) -> io::Result<()> {
+        out.write_all(b"\x1b[2J")?;
+        for (i, grid) in frames.iter().enumerate() {
+            if i > 0 {
+                std::thread::sleep(self.frame_delay);
+            }
+            out.write_all(self.frame(grid).as_bytes())?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day11::tests::generated_seat_map;
+    use crate::day11::to_grid_status;
+
+    #[test]
+    fn ppm_frames() {
+        let grid = to_grid_status("L.\n#L");
+        let palette = Palette {
+            floor: [0, 0, 0],
+            empty: [1, 2, 3],
+            taken: [9, 9, 9],
+        };
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend(&[1, 2, 3, 0, 0, 0, 9, 9, 9, 1, 2, 3]);
+        assert_eq!(expected, to_ppm(&grid, &palette, 1));
+        let scaled = to_ppm(&grid, &palette, 3);
+        assert!(scaled.starts_with(b"P6\n6 6\n255\n"));
+        assert_eq!(b"P6\n6 6\n255\n".len() + 6 * 6 * 3, scaled.len());
+
+        let dir = std::env::temp_dir().join(format!("day11-frames-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let frames = generations(&Automaton::seating_p1(), grid, 10);
+        write_ppm_frames(&frames, &dir, &palette, 1).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            let written = fs::read(dir.join(format!("frame_{:05}.ppm", i))).unwrap();
+            assert_eq!(to_ppm(frame, &palette, 1), written);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let (clear, end) = (LZW_CLEAR as usize, LZW_END as usize);
+        let fresh = (0..=end).map(|i| vec![i as u8]).collect::<Vec<_>>();
+        let (mut table, mut size, mut at) = (fresh.clone(), 3, 0);
+        let mut previous: Option<usize> = None;
+        let mut out = vec![];
+        loop {
+            let code = (0..size).fold(0, |code, bit| {
+                let pos = at + bit;
+                code | ((data[pos / 8] as usize >> (pos % 8)) & 1) << bit
+            });
+            at += size;
+            if code == clear {
+                table = fresh.clone();
+                size = 3;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code), previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = table[previous].clone();
+                    entry.push(entry[0]);
+                    entry
+                }
+                (None, None) => panic!("unknown first code {}", code),
+            };
+            if let Some(previous) = previous {
+                let mut added = table[previous].clone();
+                added.push(entry[0]);
+                table.push(added);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            out.extend(&entry);
+            previous = Some(code);
+        }
+    }
+
+    // Pulls the LZW data of every image back out of a gif written by `to_gif`.
+    fn gif_images(gif: &[u8]) -> Vec<Vec<u8>> {
+        let mut at = 6 + 7 + 4 * 3;
+        let mut images = vec![];
+        loop {
+            let is_image = match gif[at] {
+                0x21 => {
+                    at += 2;
+                    false
+                }
+                0x2c => {
+                    assert_eq!(2, gif[at + 10]);
+                    at += 11;
+                    true
+                }
+                0x3b => return images,
+                other => panic!("unexpected block {:#x}", other),
+            };
+            let mut data = vec![];
+            while gif[at] != 0 {
+                data.extend(&gif[at + 1..at + 1 + gif[at] as usize]);
+                at += 1 + gif[at] as usize;
+            }
+            at += 1;
+            if is_image {
+                images.push(data);
+            }
+        }
+    }
+
+    #[test]
+    fn gif_round_trip() {
+        let grid = to_grid_status(&generated_seat_map(90, 70, 4));
+        let frames = generations(&Automaton::seating_p2(), grid, 20);
+        let palette = Palette::default();
+        let gif = to_gif(&frames, &palette, 2, Duration::from_millis(250)).unwrap();
+        assert!(gif.starts_with(b"GIF89a\xb4\x00\x8c\x00\x91"));
+        assert_eq!(Some(&0x3b), gif.last());
+        let images = gif_images(&gif);
+        assert_eq!(frames.len(), images.len());
+        for (grid, data) in frames.iter().zip(images.iter()) {
+            assert_eq!(pixels(grid, 2, |cell| cell as u8), lzw_decode(data));
+        }
+        // 33000 cells at scale 2 is past the 65535 pixels a gif can be across
+        let wide = vec![vec![vec![GridStatus::Empty; 33_000]]];
+        let err = to_gif(&wide, &palette, 2, Duration::from_millis(250)).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(to_gif(&wide, &palette, 1, Duration::from_millis(250)).is_ok());
+    }
+
+    #[test]
+    fn lzw_table_fills_up() {
+        // noisy enough that the code table fills up and gets cleared a few times over
+        let mut state = 7u64;
+        let indices = (0..60_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 62) as u8 % 3
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, lzw_decode(&lzw_encode(&indices)));
+        assert_eq!(vec![1; 10_000], lzw_decode(&lzw_encode(&[1; 10_000])));
+        assert_eq!(Vec::<u8>::new(), lzw_decode(&lzw_encode(&[])));
+    }
+
+    #[test]
+    fn ansi_frames() {
+        let animation = TerminalAnimation {
+            palette: Palette {
+                floor: [0, 0, 0],
+                empty: [1, 2, 3],
+                taken: [4, 5, 6],
+            },
+            frame_delay: Duration::from_millis(0),
+        };
+        let grid = to_grid_status("LL.\n#..");
+        assert_eq!(
+            "\x1b[H\x1b[48;2;1;2;3m    \x1b[48;2;0;0;0m  \x1b[0m\n\
+             \x1b[48;2;4;5;6m  \x1b[48;2;0;0;0m    \x1b[0m\n",
+            animation.frame(&grid)
+        );
+        let mut out = vec![];
+        animation
+            .play(&[grid.clone(), grid.clone()], &mut out)
+            .unwrap();
+        assert_eq!(
+            format!(
+                "\x1b[2J{}{}",
+                animation.frame(&grid),
+                animation.frame(&grid)
+            ),
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use super::{Mode, Move, State, Transform, Vector};
+
+pub struct Vessel {
+    pub mode: Mode,
+    pub start: State<i64>,
+    pub moves: Vec<Move>,
+}
+
+impl Vessel {
+    // Starts where `mode` always does.
+    pub fn new(mode: Mode, moves: Vec<Move>) -> Vessel {
+        Vessel {
+            mode,
+            start: mode.start(),
+            moves,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Approach {
+    pub time: f64,
+    pub distance: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Collision {
+    pub ships: (usize, usize),
+    pub time: f64,
+    pub position: Vector<i64>,
+}
+
+// Every ship does its next move at each time step, all at once, and sits still once it runs out
+// of moves. During a step ships sail in a straight line at a steady speed (turns and waypoint
+// moves leave them where they are), so time is continuous: step t covers times t to t + 1.
+pub struct Fleet {
+    // tracks[ship][t] is where the ship is at time t
+    tracks: Vec<Vec<State<i64>>>,
+}
+
+// How far from the origin, on either axis, ships may go. Within that the exact arithmetic in
+// `meeting` and `collisions` stays inside an i64.
+const FAR: i64 = 1 << 30;
+
+fn minus(a: Vector<i64>, b: Vector<i64>) -> Vector<i64> {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+impl Fleet {
+    // Only quarter turns in the plane are supported, and ships (not waypoints) have to stay less
+    // than 2^30 from the origin on both axes. Errors count ships and moves from 1.
+    pub fn simulate(vessels: &[Vessel]) -> Result<Fleet, String> {
+        let steps = vessels.iter().map(|v| v.moves.len()).max().unwrap_or(0);
+        let near = |state: &State<i64>| state.position.iter().all(|c| c.abs() < FAR);
+        let mut tracks = vec![];
+        for (ship, vessel) in vessels.iter().enumerate() {
+            if !near(&vessel.start) {
+                return Err(format!("ship {} starts too far out", ship + 1));
+            }
+            let mut track = vec![vessel.start];
+            for (step, mov) in vessel.moves.iter().enumerate() {
+                let transform = Transform::of_move(mov, vessel.mode).map_err(|why| {
+                    format!("ship {}, move {}: {:?} {}", ship + 1, step + 1, mov, why)
+                })?;
+                let next = transform
+                    .checked_apply(&track[step])
+                    .filter(near)
+                    .ok_or_else(|| {
+                        format!(
+                            "ship {}, move {}: {:?} goes too far out",
+                            ship + 1,
+                            step + 1,
+                            mov
+                        )
+                    })?;
+                track.push(next);
+            }
+            track.resize(steps + 1, track[track.len() - 1]);
+            tracks.push(track);
+        }
+        Ok(Fleet { tracks })
+    }
+
+    pub fn ships(&self) -> usize {
+        self.tracks.len()
+    }
+
+    // Number of time steps until every ship has run out of moves.
+    pub fn duration(&self) -> usize {
+        self.tracks.first().map_or(0, |track| track.len() - 1)
+    }
+
+    pub fn state_at(&self, ship: usize, time: usize) -> State<i64> {
+        self.tracks[ship][time]
+    }
+
+    // Where `b` is relative to `a` at the start and end of step `t`.
+    fn gap(&self, a: usize, b: usize, t: usize) -> (Vector<i64>, Vector<i64>) {
+        let (track_a, track_b) = (&self.tracks[a], &self.tracks[b]);
+        (
+            minus(track_b[t].position, track_a[t].position),
+            minus(track_b[t + 1].position, track_a[t + 1].position),
+        )
+    }
+
+    // Closest `a` and `b` ever get (in a straight line), and the earliest time they're that close.
+    pub fn closest_approach(&self, a: usize, b: usize) -> Approach {
+        let at_start = minus(self.tracks[b][0].position, self.tracks[a][0].position);
+        let mut best = Approach {
+            time: 0.0,
+            distance: (at_start[0] as f64).hypot(at_start[1] as f64),
+        };
+        for t in 0..self.duration() {
+            let (from, to) = self.gap(a, b, t);
+            let from = [from[0] as f64, from[1] as f64];
+            let change = [to[0] as f64 - from[0], to[1] as f64 - from[1]];
+            let speed = change[0] * change[0] + change[1] * change[1];
+            let s = if speed == 0.0 {
+                0.0
+            } else {
+                (-(from[0] * change[0] + from[1] * change[1]) / speed).clamp(0.0, 1.0)
+            };
+            let distance = (from[0] + s * change[0]).hypot(from[1] + s * change[1]);
+            if distance < best.distance {
+                best = Approach {
+                    time: t as f64 + s,
+                    distance,
+                };
+            }
+        }
+        best
+    }
+
+    pub fn closest_approaches(&self) -> Vec<((usize, usize), Approach)> {
+        self.pairs()
+            .map(|(a, b)| ((a, b), self.closest_approach(a, b)))
+            .collect()
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (usize, usize)> {
+        let ships = self.ships();
+        (0..ships).flat_map(move |a| (a + 1..ships).map(move |b| (a, b)))
+    }
+
+    // When during step `t`, as a fraction num / den of it in [0, 1), `a` and `b` are in the same
+    // place, if they ever are. Ships keeping each other company all step long meet at its start.
+    fn meeting(&self, a: usize, b: usize, t: usize) -> Option<(i64, i64)> {
+        let (from, to) = self.gap(a, b, t);
+        let mut when: Option<(i64, i64)> = None;
+        for axis in 0..2 {
+            let change = to[axis] - from[axis];
+            let (num, den) = match change {
+                0 if from[axis] == 0 => continue,
+                0 => return None,
+                c if c > 0 => (-from[axis], c),
+                c => (from[axis], -c),
+            };
+            match when {
+                Some((n, d)) if n * den != num * d => return None,
+                _ => when = Some((num, den)),
+            }
+        }
+        match when.unwrap_or((0, 1)) {
+            (num, den) if 0 <= num && num < den => Some((num, den)),
+            _ => None,
+        }
+    }
+
+    // Every time two ships are in the same place and that place is on integer coordinates, at
+    // most once per pair per time step (the earliest), in order of time.
+    pub fn collisions(&self) -> Vec<Collision> {
+        let mut collisions = vec![];
+        for (a, b) in self.pairs() {
+            let track = &self.tracks[a];
+            for t in 0..self.duration() {
+                let (num, den) = match self.meeting(a, b, t) {
+                    Some(when) => when,
+                    None => continue,
+                };
+                let (from, to) = (track[t].position, track[t + 1].position);
+                let scaled = [
+                    from[0] * den + num * (to[0] - from[0]),
+                    from[1] * den + num * (to[1] - from[1]),
+                ];
+                if scaled[0] % den == 0 && scaled[1] % den == 0 {
+                    collisions.push(Collision {
+                        ships: (a, b),
+                        time: t as f64 + num as f64 / den as f64,
+                        position: [scaled[0] / den, scaled[1] / den],
+                    });
+                }
+            }
+            let end = self.duration();
+            if track[end].position == self.tracks[b][end].position {
+                collisions.push(Collision {
+                    ships: (a, b),
+                    time: end as f64,
+                    position: track[end].position,
+                });
+            }
+        }
+        collisions.sort_by(|x, y| x.time.partial_cmp(&y.time).unwrap());
+        collisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12::parse_input;
+    use Move::*;
+
+    fn vessel(mode: Mode, position: Vector<i64>, heading: Vector<i64>, moves: &str) -> Vessel {
+        Vessel {
+            mode,
+            start: State {
+                position,
+                vector: heading,
+            },
+            moves: parse_input(moves),
+        }
+    }
+
+    #[test]
+    fn head_on() {
+        // one meets the other halfway, on (5, 0)
+        let fleet = Fleet::simulate(&[
+            vessel(Mode::Ship, [0, 0], [1, 0], "F10"),
+            vessel(Mode::Ship, [10, 0], [-1, 0], "F10"),
+        ])
+        .unwrap();
+        assert_eq!(
+            Approach {
+                time: 0.5,
+                distance: 0.0
+            },
+            fleet.closest_approach(0, 1)
+        );
+        assert_eq!(
+            vec![Collision {
+                ships: (0, 1),
+                time: 0.5,
+                position: [5, 0]
+            }],
+            fleet.collisions()
+        );
+
+        // now they pass each other at (4.5, 0), which isn't a collision on integer coordinates
+        let fleet = Fleet::simulate(&[
+            vessel(Mode::Ship, [0, 0], [1, 0], "F10"),
+            vessel(Mode::Ship, [9, 0], [-1, 0], "F10"),
+        ])
+        .unwrap();
+        assert_eq!(0.0, fleet.closest_approach(0, 1).distance);
+        assert!((fleet.closest_approach(0, 1).time - 0.45).abs() < 1e-12);
+        assert!(fleet.collisions().is_empty());
+    }
+
+    #[test]
+    fn lockstep_fleet() {
+        let fleet = Fleet::simulate(&[
+            // east along y = 0, two steps of 3
+            vessel(Mode::Ship, [0, 0], [1, 0], "F3\nF3"),
+            // spends a step turning south, then comes down from (4, 3) to (4, 0)
+            vessel(Mode::Ship, [4, 3], [1, 0], "R90\nF3"),
+            // east along y = 3 onto (4, 3), just as the one above starts down, then stops
+            vessel(Mode::Ship, [0, 3], [1, 0], "F4"),
+        ])
+        .unwrap();
+        assert_eq!(2, fleet.duration());
+        assert_eq!([4, 3], fleet.state_at(2, 2).position);
+        let approaches = fleet.closest_approaches();
+        assert_eq!(
+            vec![(0, 1), (0, 2), (1, 2)],
+            approaches.iter().map(|(pair, _)| *pair).collect::<Vec<_>>()
+        );
+        // ship 1 is one down and one left of ship 0's line two thirds into the second step
+        assert!((approaches[0].1.time - 5.0 / 3.0).abs() < 1e-12);
+        assert!((approaches[0].1.distance - 2f64.sqrt()).abs() < 1e-12);
+        assert_eq!(
+            Approach {
+                time: 0.0,
+                distance: 3.0
+            },
+            approaches[1].1
+        );
+        assert_eq!(
+            Approach {
+                time: 1.0,
+                distance: 0.0
+            },
+            approaches[2].1
+        );
+        assert_eq!(
+            vec![Collision {
+                ships: (1, 2),
+                time: 1.0,
+                position: [4, 3]
+            }],
+            fleet.collisions()
+        );
+    }
+
+    #[test]
+    fn waypoint_ships_and_sitting_together() {
+        let moves = parse_input("F10\nN3\nF7\nR90\nF11");
+        let fleet = Fleet::simulate(&[
+            Vessel::new(Mode::Ship, moves.clone()),
+            Vessel::new(Mode::Waypoint, moves.clone()),
+            Vessel::new(Mode::Waypoint, moves),
+        ])
+        .unwrap();
+        // the two waypoint ships sail together the whole time, and all three start together
+        let collisions = fleet.collisions();
+        assert_eq!(
+            5 + 1,
+            collisions.iter().filter(|c| c.ships == (1, 2)).count()
+        );
+        assert_eq!(
+            vec![(0, 1), (0, 2)],
+            collisions
+                .iter()
+                .filter(|c| c.ships != (1, 2))
+                .map(|c| c.ships)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!([214, -72], fleet.state_at(1, fleet.duration()).position);
+        assert!(Fleet::simulate(&[Vessel::new(Mode::Ship, vec![L(45)])]).is_err());
+        assert_eq!(
+            Some("ship 2, move 3: D(1) only works in 3D".to_string()),
+            Fleet::simulate(&[
+                Vessel::new(Mode::Ship, vec![F(1)]),
+                Vessel::new(Mode::Ship, vec![F(1), L(90), D(1)])
+            ])
+            .err()
+        );
+        // the waypoint can go as far as it likes, but the ship can't follow it
+        let far = parse_input("N1000000000\nE1000000000");
+        assert!(Fleet::simulate(&[Vessel::new(Mode::Waypoint, far)]).is_ok());
+        assert_eq!(
+            Some("ship 1, move 2: F(2) goes too far out".to_string()),
+            Fleet::simulate(&[Vessel::new(Mode::Waypoint, vec![N(1 << 29), F(2)])]).err()
+        );
+    }
+}
@@ -1,7 +1,16 @@
-use regex::Regex;
+pub mod fleet;
+pub mod obstacles;
+pub mod segments;
+pub mod simplify;
+pub mod space;
+pub mod trajectory;
 
-#[derive(Debug, PartialEq, Eq)]
-enum Move {
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Move {
     N(i32),
     S(i32),
     E(i32),
@@ -9,117 +18,442 @@ enum Move {
     L(i32),
     R(i32),
     F(i32),
+    // 3D only (see `space`): up, down, pitch the nose up and bank (roll) to the right
+    U(i32),
+    D(i32),
+    P(i32),
+    B(i32),
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct Ship {
-    dir: i32,
-    x: i32,
-    y: i32,
-    waypoint_x: i32,
-    waypoint_y: i32,
-}
-
-impl Move {
-    fn of_dir_and_value(dir: &str, val: i32) -> Move {
-        match dir {
-            "N" => Move::N(val),
-            "S" => Move::S(val),
-            "E" => Move::E(val),
-            "W" => Move::W(val),
-            "L" => Move::L(val),
-            "R" => Move::R(val),
-            "F" => Move::F(val),
-            _ => panic!("unknown dir"),
-        }
-    }
-}
-
-fn parse_input(input: &str) -> Vec<Move> {
-    Regex::new(r"([NSEWLRF])(\d*)")
-        .unwrap()
-        .captures_iter(input)
-        .map(|cap| Move::of_dir_and_value(&cap[1], str::parse::<i32>(&cap[2]).unwrap()))
-        .collect::<Vec<_>>()
-}
-
-fn sim_moves(ship: &mut Ship, moves: &[Move]) {
-    for mov in moves {
-        match mov {
-            Move::N(amt) => ship.y += amt,
-            Move::S(amt) => ship.y -= amt,
-            Move::E(amt) => ship.x += amt,
-            Move::W(amt) => ship.x -= amt,
-            Move::L(amt) => ship.dir = (ship.dir - amt + 360) % 360,
-            Move::R(amt) => ship.dir = (ship.dir + amt + 360) % 360,
-            Move::F(amt) => match ship.dir {
-                0 => ship.y += amt,
-                90 => ship.x += amt,
-                180 => ship.y -= amt,
-                270 => ship.x -= amt,
-                other => panic!("lol, no non-90 value {} found", other),
-            },
+// Whether the parser lets through U, D, P and B.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dimensions {
+    Plane,
+    Space,
+}
+
+// Which turns the parser lets through.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Turns {
+    // multiples of 90 degrees, which integer coordinates can do exactly
+    Quarter,
+    // any whole number of degrees
+    Any,
+}
+
+// One instruction per line, a letter and then a non-negative number with nothing in between.
+// Blank lines and whitespace around an instruction are fine; anything else is an error saying
+// where (both counting from 1).
+pub fn parse_moves(input: &str, dimensions: Dimensions, turns: Turns) -> Result<Vec<Move>, String> {
+    Ok(parse_numbered(input, dimensions, turns)?
+        .into_iter()
+        .map(|(_, mov)| mov)
+        .collect())
+}
+
+// `parse_moves`, with the line (counting from 1) each move is on.
+pub fn parse_numbered(
+    input: &str,
+    dimensions: Dimensions,
+    turns: Turns,
+) -> Result<Vec<(usize, Move)>, String> {
+    let mut moves = vec![];
+    for (row, line) in input.lines().enumerate() {
+        let instruction = line.trim();
+        if instruction.is_empty() {
+            continue;
+        }
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        let at = |col: usize| format!("line {}, column {}", row + 1, indent + col + 1);
+        let mut chars = instruction.chars();
+        let dir = chars.next().unwrap();
+        let value = chars.as_str();
+        let of_value: fn(i32) -> Move = match dir {
+            'N' => Move::N,
+            'S' => Move::S,
+            'E' => Move::E,
+            'W' => Move::W,
+            'L' => Move::L,
+            'R' => Move::R,
+            'F' => Move::F,
+            'U' => Move::U,
+            'D' => Move::D,
+            'P' => Move::P,
+            'B' => Move::B,
+            _ => return Err(format!("{}: unknown instruction {:?}", at(0), dir)),
+        };
+        match value.chars().enumerate().find(|(_, c)| !c.is_ascii_digit()) {
+            _ if value.is_empty() => return Err(format!("{}: missing value", at(1))),
+            Some((0, '-')) => return Err(format!("{}: negative value", at(1))),
+            Some((col, c)) => return Err(format!("{}: unexpected {:?}", at(col + 1), c)),
+            None => {}
+        }
+        let amt = value
+            .parse::<i32>()
+            .map_err(|_| format!("{}: {} is too large", at(1), value))?;
+        let mov = of_value(amt);
+        let three_d = matches!(mov, Move::U(_) | Move::D(_) | Move::P(_) | Move::B(_));
+        if three_d && dimensions == Dimensions::Plane {
+            return Err(format!("{}: {:?} only works in 3D", at(0), dir));
+        }
+        let turn = matches!(mov, Move::L(_) | Move::R(_) | Move::P(_) | Move::B(_));
+        if turn && turns == Turns::Quarter && amt % 90 != 0 {
+            return Err(format!("{}: {} degrees isn't a multiple of 90", at(1), amt));
         }
+        moves.push((row + 1, mov));
     }
+    Ok(moves)
 }
 
-fn sim_moves_p2(ship: &mut Ship, moves: &[Move]) {
-    for mov in moves {
-        match mov {
-            Move::N(amt) => ship.waypoint_y += amt,
-            Move::S(amt) => ship.waypoint_y -= amt,
-            Move::E(amt) => ship.waypoint_x += amt,
-            Move::W(amt) => ship.waypoint_x -= amt,
-            Move::L(amt) => {
-                let mut turns = *amt;
-                while turns > 0 {
-                    let tmp = ship.waypoint_x;
-                    ship.waypoint_x = -ship.waypoint_y;
-                    ship.waypoint_y = tmp;
-                    turns -= 90;
-                }
+// The puzzle's own instructions, in the plane.
+pub fn parse_input(input: &str) -> Vec<Move> {
+    parse_moves(input, Dimensions::Plane, Turns::Any)
+        .unwrap_or_else(|err| panic!("bad instructions: {}", err))
+}
+
+// Numbers the navigation engine can run on.
+pub trait Scalar:
+    Copy
+    + PartialEq
+    + Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+{
+    fn from_i32(n: i32) -> Self;
+    fn to_f64(self) -> f64;
+    // cos and sin of an anticlockwise turn by `degrees`, or None if this type can't hold them
+    fn cos_sin(degrees: i32) -> Option<(Self, Self)>;
+}
+
+fn quarter_turn(degrees: i32) -> Option<(i32, i32)> {
+    match degrees.rem_euclid(360) {
+        0 => Some((1, 0)),
+        90 => Some((0, 1)),
+        180 => Some((-1, 0)),
+        270 => Some((0, -1)),
+        _ => None,
+    }
+}
+
+// Integers only do quarter turns, and can say when they overflow.
+pub trait Checked: Scalar {
+    fn add_checked(self, other: Self) -> Option<Self>;
+    fn mul_checked(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! integer_scalar {
+    ($($int:ty),*) => {$(
+        impl Scalar for $int {
+            fn from_i32(n: i32) -> $int {
+                n as $int
             }
-            Move::R(amt) => {
-                let mut turns = *amt;
-                while turns > 0 {
-                    let tmp = ship.waypoint_x;
-                    ship.waypoint_x = ship.waypoint_y;
-                    ship.waypoint_y = -tmp;
-                    turns -= 90;
-                }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn cos_sin(degrees: i32) -> Option<($int, $int)> {
+                quarter_turn(degrees).map(|(cos, sin)| (cos as $int, sin as $int))
+            }
+        }
+
+        impl Checked for $int {
+            fn add_checked(self, other: $int) -> Option<$int> {
+                self.checked_add(other)
+            }
+
+            fn mul_checked(self, other: $int) -> Option<$int> {
+                self.checked_mul(other)
+            }
+        }
+    )*};
+}
+
+integer_scalar!(i32, i64, i128);
+
+// Quarter turns stay exact; anything else goes through sin and cos.
+impl Scalar for f64 {
+    fn from_i32(n: i32) -> f64 {
+        n as f64
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn cos_sin(degrees: i32) -> Option<(f64, f64)> {
+        Some(match quarter_turn(degrees) {
+            Some((cos, sin)) => (cos as f64, sin as f64),
+            None => {
+                let radians = (degrees as f64).to_radians();
+                (radians.cos(), radians.sin())
+            }
+        })
+    }
+}
+
+pub type Vector<T> = [T; 2];
+pub type Matrix<T> = [[T; 2]; 2];
+
+fn add<T: Scalar>(u: Vector<T>, v: Vector<T>) -> Vector<T> {
+    [u[0] + v[0], u[1] + v[1]]
+}
+
+fn mat_vec<T: Scalar>(m: &Matrix<T>, v: Vector<T>) -> Vector<T> {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1],
+        m[1][0] * v[0] + m[1][1] * v[1],
+    ]
+}
+
+fn checked_add<T: Checked>(u: Vector<T>, v: Vector<T>) -> Option<Vector<T>> {
+    Some([u[0].add_checked(v[0])?, u[1].add_checked(v[1])?])
+}
+
+fn checked_mat_vec<T: Checked>(m: &Matrix<T>, v: Vector<T>) -> Option<Vector<T>> {
+    let row = |r: &[T; 2]| r[0].mul_checked(v[0])?.add_checked(r[1].mul_checked(v[1])?);
+    Some([row(&m[0])?, row(&m[1])?])
+}
+
+fn mat_mul<T: Scalar>(m: &Matrix<T>, n: &Matrix<T>) -> Matrix<T> {
+    let column = |c: usize| mat_vec(m, [n[0][c], n[1][c]]);
+    let (first, second) = (column(0), column(1));
+    [[first[0], second[0]], [first[1], second[1]]]
+}
+
+fn mat_add<T: Scalar>(m: &Matrix<T>, n: &Matrix<T>) -> Matrix<T> {
+    [add(m[0], n[0]), add(m[1], n[1])]
+}
+
+// x is east and y is north. In ship mode NSEW move the ship and `vector` is the heading, a unit
+// vector; in waypoint mode NSEW move the waypoint and `vector` is where it is relative to the ship.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct State<T> {
+    pub position: Vector<T>,
+    pub vector: Vector<T>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Ship,
+    Waypoint,
+}
+
+impl Mode {
+    pub fn start<T: Scalar>(self) -> State<T> {
+        let zero = T::from_i32(0);
+        State {
+            position: [zero, zero],
+            vector: match self {
+                Mode::Ship => [T::from_i32(1), zero],
+                Mode::Waypoint => [T::from_i32(10), T::from_i32(1)],
+            },
+        }
+    }
+}
+
+// Why the plane engine can't do a move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unsupported {
+    // U, D, P or B; see `space`
+    ThreeD,
+    // a turn the number type can't do exactly
+    InexactTurn,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unsupported::ThreeD => write!(f, "only works in 3D"),
+            Unsupported::InexactTurn => write!(f, "isn't a quarter turn"),
+        }
+    }
+}
+
+// Every move, in either mode, takes (position, vector) to
+//   position + forward * vector + shift, turn * vector + nudge
+// and doing one such map after another gives another one of the same shape.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform<T> {
+    pub forward: Matrix<T>,
+    pub shift: Vector<T>,
+    pub turn: Matrix<T>,
+    pub nudge: Vector<T>,
+}
+
+impl<T: Scalar> Transform<T> {
+    pub fn identity() -> Transform<T> {
+        let (zero, one) = (T::from_i32(0), T::from_i32(1));
+        Transform {
+            forward: [[zero; 2]; 2],
+            shift: [zero; 2],
+            turn: [[one, zero], [zero, one]],
+            nudge: [zero; 2],
+        }
+    }
+
+    pub fn of_move(mov: &Move, mode: Mode) -> Result<Transform<T>, Unsupported> {
+        let mut transform = Transform::identity();
+        let zero = T::from_i32(0);
+        let translation = match *mov {
+            Move::N(amt) => [zero, T::from_i32(amt)],
+            Move::S(amt) => [zero, -T::from_i32(amt)],
+            Move::E(amt) => [T::from_i32(amt), zero],
+            Move::W(amt) => [-T::from_i32(amt), zero],
+            Move::L(amt) | Move::R(amt) => {
+                let degrees = if let Move::L(_) = mov { amt } else { -amt };
+                let (cos, sin) = T::cos_sin(degrees).ok_or(Unsupported::InexactTurn)?;
+                transform.turn = [[cos, -sin], [sin, cos]];
+                return Ok(transform);
             }
             Move::F(amt) => {
-                ship.x += ship.waypoint_x * amt;
-                ship.y += ship.waypoint_y * amt;
+                let amt = T::from_i32(amt);
+                transform.forward = [[amt, zero], [zero, amt]];
+                return Ok(transform);
+            }
+            Move::U(_) | Move::D(_) | Move::P(_) | Move::B(_) => return Err(Unsupported::ThreeD),
+        };
+        match mode {
+            Mode::Ship => transform.shift = translation,
+            Mode::Waypoint => transform.nudge = translation,
+        }
+        Ok(transform)
+    }
+
+    // This transform followed by `next`.
+    pub fn then(&self, next: &Transform<T>) -> Transform<T> {
+        Transform {
+            forward: mat_add(&self.forward, &mat_mul(&next.forward, &self.turn)),
+            shift: add(
+                add(self.shift, mat_vec(&next.forward, self.nudge)),
+                next.shift,
+            ),
+            turn: mat_mul(&next.turn, &self.turn),
+            nudge: add(mat_vec(&next.turn, self.nudge), next.nudge),
+        }
+    }
+
+    pub fn apply(&self, state: &State<T>) -> State<T> {
+        State {
+            position: add(
+                add(state.position, mat_vec(&self.forward, state.vector)),
+                self.shift,
+            ),
+            vector: add(mat_vec(&self.turn, state.vector), self.nudge),
+        }
+    }
+}
+
+impl<T: Checked> Transform<T> {
+    // `apply`, or None if anything overflows on the way.
+    pub fn checked_apply(&self, state: &State<T>) -> Option<State<T>> {
+        Some(State {
+            position: checked_add(
+                checked_add(
+                    state.position,
+                    checked_mat_vec(&self.forward, state.vector)?,
+                )?,
+                self.shift,
+            )?,
+            vector: checked_add(checked_mat_vec(&self.turn, state.vector)?, self.nudge)?,
+        })
+    }
+}
+
+pub fn simulate<T: Scalar>(
+    moves: &[Move],
+    mode: Mode,
+    start: State<T>,
+) -> Result<State<T>, Unsupported> {
+    moves.iter().try_fold(start, |state, mov| {
+        Transform::of_move(mov, mode).map(|transform| transform.apply(&state))
+    })
+}
+
+// `simulate` that stops at the first move it can't do or that overflows `T`, and says which one it
+// was (counting from 1).
+pub fn simulate_checked<T: Checked>(
+    moves: &[Move],
+    mode: Mode,
+    start: State<T>,
+) -> Result<State<T>, String> {
+    simulate_checked_at(moves, mode, start, |i| format!("move {}", i + 1))
+}
+
+// `simulate_checked` with `place(i)` saying where move i is in its errors.
+fn simulate_checked_at<T: Checked>(
+    moves: &[Move],
+    mode: Mode,
+    start: State<T>,
+    place: impl Fn(usize) -> String,
+) -> Result<State<T>, String> {
+    moves.iter().enumerate().try_fold(start, |state, (i, mov)| {
+        Transform::of_move(mov, mode)
+            .map_err(|why| format!("{}: {:?} {}", place(i), mov, why))?
+            .checked_apply(&state)
+            .ok_or_else(|| {
+                format!(
+                    "{}: {:?} overflows {}",
+                    place(i),
+                    mov,
+                    std::any::type_name::<T>()
+                )
+            })
+    })
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Navigation {
+    Exact(State<i64>),
+    Approximate(State<f64>),
+}
+
+impl Navigation {
+    pub fn manhattan(&self) -> f64 {
+        match self {
+            Navigation::Exact(state) => {
+                state.position.iter().map(|c| c.unsigned_abs() as f64).sum()
             }
+            Navigation::Approximate(state) => state.position[0].abs() + state.position[1].abs(),
         }
     }
 }
 
+// Works in integers as long as every turn is a multiple of 90 degrees and nothing overflows an
+// i64, in floating point otherwise. Panics on 3D moves, which need `space::navigate`.
+pub fn navigate(moves: &[Move], mode: Mode) -> Navigation {
+    match simulate_checked(moves, mode, mode.start()) {
+        Ok(state) => Navigation::Exact(state),
+        Err(_) => Navigation::Approximate(
+            simulate(moves, mode, mode.start()).unwrap_or_else(|why| panic!("a move {}", why)),
+        ),
+    }
+}
+
+// Errors say which line of `input` they're about.
+fn exact_manhattan(input: &str, mode: Mode) -> Result<i32, String> {
+    let numbered = parse_numbered(input, Dimensions::Plane, Turns::Quarter)
+        .map_err(|err| format!("bad instructions: {}", err))?;
+    let moves = numbered.iter().map(|(_, mov)| *mov).collect::<Vec<_>>();
+    let state = simulate_checked_at::<i64>(&moves, mode, mode.start(), |i| {
+        format!("line {}", numbered[i].0)
+    })?;
+    let [x, y] = state.position;
+    x.checked_abs()
+        .zip(y.checked_abs())
+        .and_then(|(x, y)| x.checked_add(y))
+        .and_then(|distance| i32::try_from(distance).ok())
+        .ok_or_else(|| format!("the distance to {:?} doesn't fit in an i32", state.position))
+}
+
 pub fn solve_p1(input: &str) -> i32 {
-    let ship = &mut Ship {
-        dir: 90,
-        x: 0,
-        y: 0,
-        waypoint_x: 10,
-        waypoint_y: 1,
-    };
-    let moves = parse_input(input);
-    sim_moves(ship, &moves);
-    ship.x.abs() + ship.y.abs()
+    exact_manhattan(input, Mode::Ship).unwrap_or_else(|err| panic!("{}", err))
 }
 
 pub fn solve_p2(input: &str) -> i32 {
-    let ship = &mut Ship {
-        dir: 90,
-        x: 0,
-        y: 0,
-        waypoint_x: 10,
-        waypoint_y: 1,
-    };
-    let moves = parse_input(input);
-    sim_moves_p2(ship, &moves);
-    ship.x.abs() + ship.y.abs()
+    exact_manhattan(input, Mode::Waypoint).unwrap_or_else(|err| panic!("{}", err))
 }
 
 #[cfg(test)]
@@ -142,6 +476,43 @@ F11";
         );
     }
 
+    #[test]
+    fn strict_parsing() {
+        use Move::*;
+        assert_eq!(
+            Ok(vec![F(10), L(45), U(0), B(180)]),
+            parse_moves(
+                "F10\r\n  L45  \n\nU0\nB180\n",
+                Dimensions::Space,
+                Turns::Any
+            )
+        );
+        let error = |input| parse_moves(input, Dimensions::Plane, Turns::Quarter).unwrap_err();
+        assert_eq!("line 2, column 1: unknown instruction 'X'", error("F1\nX3"));
+        assert_eq!("line 1, column 2: missing value", error("N\nF2"));
+        assert_eq!("line 3, column 4: missing value", error("F1\n\n  S"));
+        assert_eq!("line 1, column 2: negative value", error("E-5"));
+        assert_eq!("line 1, column 4: unexpected 'F'", error("F10F3"));
+        assert_eq!("line 1, column 3: unexpected ' '", error("R9 0"));
+        assert_eq!(
+            "line 1, column 2: 99999999999 is too large",
+            error("W99999999999")
+        );
+        assert_eq!(
+            "line 2, column 3: 45 degrees isn't a multiple of 90",
+            error("F1\n L45")
+        );
+        assert_eq!("line 2, column 1: 'U' only works in 3D", error("F2\nU3"));
+        assert_eq!(
+            Err("line 1, column 2: 30 degrees isn't a multiple of 90".to_string()),
+            parse_moves("P30", Dimensions::Space, Turns::Quarter)
+        );
+        assert_eq!(
+            Ok(vec![R(270), F(45)]),
+            parse_moves("R270\nF45", Dimensions::Plane, Turns::Quarter)
+        );
+    }
+
     #[test]
     fn test_p1() {
         assert_eq!(25, solve_p1(SAMPLE_INPUT));
@@ -151,4 +522,141 @@ F11";
     fn test_p2() {
         assert_eq!(286, solve_p2(SAMPLE_INPUT));
     }
+
+    fn close_to(expected: [f64; 2], got: [f64; 2]) -> bool {
+        (expected[0] - got[0]).abs() < 1e-9 && (expected[1] - got[1]).abs() < 1e-9
+    }
+
+    #[test]
+    fn any_angle_turns() {
+        use Move::*;
+        let half = 0.5f64.sqrt();
+        // north-east for 10, then due south for 2
+        match navigate(&[L(45), F(10), R(135), F(2)], Mode::Ship) {
+            Navigation::Approximate(state) => {
+                assert!(close_to([10.0 * half, 10.0 * half - 2.0], state.position));
+                assert!(close_to([0.0, -1.0], state.vector));
+            }
+            exact => panic!("expected an approximate result, got {:?}", exact),
+        }
+        // two eighth turns make a quarter turn, which old `sim_moves_p2` got wrong
+        match (
+            navigate(&[R(45), R(45), F(1)], Mode::Waypoint),
+            navigate(&[R(90), F(1)], Mode::Waypoint),
+        ) {
+            (Navigation::Approximate(eighths), Navigation::Exact(quarter)) => {
+                assert!(close_to([1.0, -10.0], eighths.position));
+                assert_eq!([1, -10], quarter.position);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            navigate(&[R(270), F(3), L(450), F(4)], Mode::Ship),
+            navigate(&[L(90), F(3), L(90), F(4)], Mode::Ship)
+        );
+        assert_eq!(
+            7.0,
+            navigate(&[L(90), F(3), L(90), F(4)], Mode::Ship).manhattan()
+        );
+    }
+
+    #[test]
+    fn integer_widths() {
+        let moves = parse_input(SAMPLE_INPUT);
+        assert_eq!(
+            Ok([17, -8]),
+            simulate::<i32>(&moves, Mode::Ship, Mode::Ship.start()).map(|s| s.position)
+        );
+        assert_eq!(
+            Ok([214, -72]),
+            simulate_checked::<i128>(&moves, Mode::Waypoint, Mode::Waypoint.start())
+                .map(|s| s.position)
+        );
+        // the waypoint itself still fits in an i32, but going to it twice doesn't
+        let moves = parse_input("N2000000000\nF2\nF1");
+        assert_eq!(
+            Err("move 2: F(2) overflows i32".to_string()),
+            simulate_checked::<i32>(&moves, Mode::Waypoint, Mode::Waypoint.start())
+        );
+        assert_eq!(
+            Ok([30, 6000000003]),
+            simulate_checked::<i64>(&moves, Mode::Waypoint, Mode::Waypoint.start())
+                .map(|s| s.position)
+        );
+        // a long route that fits in an i128, but only two of its moves fit in an i64
+        let long = vec![Move::F(i32::MAX); 1 << 20];
+        let start = State {
+            position: [0i64, 0],
+            vector: [i32::MAX as i64; 2],
+        };
+        assert_eq!(
+            Err("move 3: F(2147483647) overflows i64".to_string()),
+            simulate_checked(&long, Mode::Waypoint, start)
+        );
+        let start = State {
+            position: [0i128, 0],
+            vector: [i32::MAX as i128; 2],
+        };
+        assert_eq!(
+            Ok([(i32::MAX as i128).pow(2) << 20; 2]),
+            simulate_checked(&long, Mode::Waypoint, start).map(|s| s.position)
+        );
+        assert_eq!(
+            Err("move 2: U(3) only works in 3D".to_string()),
+            simulate_checked::<i64>(&[Move::F(1), Move::U(3)], Mode::Ship, Mode::Ship.start())
+        );
+        assert_eq!(
+            Err("move 1: L(45) isn't a quarter turn".to_string()),
+            simulate_checked::<i64>(&[Move::L(45)], Mode::Ship, Mode::Ship.start())
+        );
+    }
+
+    #[test]
+    fn errors_name_lines() {
+        // blank lines still count
+        assert_eq!(
+            Err("line 6: F(2147483647) overflows i64".to_string()),
+            exact_manhattan(
+                "\nN2000000000\n\nF2147483647\nF2147483647\nF2147483647",
+                Mode::Waypoint
+            )
+        );
+        assert_eq!(
+            Err("the distance to [4294967294, 0] doesn't fit in an i32".to_string()),
+            exact_manhattan("\nF2147483647\n\nF2147483647", Mode::Ship)
+        );
+        assert_eq!(
+            Err(
+                "bad instructions: line 3, column 2: 45 degrees isn't a multiple of 90".to_string()
+            ),
+            exact_manhattan("\nF1\nL45", Mode::Ship)
+        );
+        assert_eq!(
+            Ok(vec![(2, Move::F(10)), (3, Move::N(3)), (4, Move::F(7))]),
+            parse_numbered("\nF10\nN3\n  F7\n", Dimensions::Plane, Turns::Quarter)
+        );
+        // too big for integers, so floats
+        match navigate(
+            &parse_input("N2000000000\nF2147483647\nF2147483647\nF2147483647"),
+            Mode::Waypoint,
+        ) {
+            Navigation::Approximate(state) => assert!(state.position[1] > 1.2e19),
+            exact => panic!("expected an approximate result, got {:?}", exact),
+        }
+    }
+
+    #[test]
+    fn transforms_compose() {
+        let moves = parse_input("F10\nN3\nF7\nR90\nF11\nL270\nW4\nS2\nR180\nF3\nE8");
+        for mode in [Mode::Ship, Mode::Waypoint].iter() {
+            let composed = moves.iter().fold(Transform::identity(), |acc, mov| {
+                acc.then(&Transform::of_move(mov, *mode).unwrap())
+            });
+            let start = State {
+                position: [3i64, -7],
+                vector: [2, 5],
+            };
+            assert_eq!(simulate(&moves, *mode, start), Ok(composed.apply(&start)));
+        }
+    }
 }
@@ -0,0 +1,470 @@
+use super::{Mode, Move, State, Transform, Vector};
+use crate::day11::{GridFormat, GridStatus};
+use crate::day13::number_theory::gcd;
+use std::collections::VecDeque;
+
+// A patch of the plane with rocks on it, in day11's format: `#` is a rock, `.` (or `L`) open
+// water. Row 0 is the northernmost; the top-left cell sits at `origin` and going down a row goes
+// one south. Everything off the map is open water.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObstacleMap {
+    pub origin: Vector<i64>,
+    width: usize,
+    height: usize,
+    rocks: Vec<bool>,
+}
+
+impl ObstacleMap {
+    pub fn parse(input: &str) -> Result<ObstacleMap, String> {
+        let grid = GridFormat::default().parse(input)?;
+        Ok(ObstacleMap {
+            origin: [0, 0],
+            width: grid.first().map_or(0, Vec::len),
+            height: grid.len(),
+            rocks: grid
+                .iter()
+                .flatten()
+                .map(|cell| *cell == GridStatus::Taken)
+                .collect(),
+        })
+    }
+
+    pub fn with_origin(mut self, origin: Vector<i64>) -> ObstacleMap {
+        self.origin = origin;
+        self
+    }
+
+    pub fn is_blocked(&self, [x, y]: Vector<i64>) -> bool {
+        let (col, row) = (x - self.origin[0], self.origin[1] - y);
+        (0..self.width as i64).contains(&col)
+            && (0..self.height as i64).contains(&row)
+            && self.rocks[row as usize * self.width + col as usize]
+    }
+}
+
+// What happens when a move would take the ship onto a rock.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    // the ship stays just short of the rock and the rest of the route is dropped
+    Stop,
+    // the ship stays just short of the rock and carries on with the next move
+    Clip,
+    // the whole thing fails
+    Error,
+}
+
+// Where `sail` leaves the ship. Under `Policy::Stop`, `stopped_at` is the index in `moves` of the
+// move that would have run onto a rock, and None if the whole route was sailed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Voyage {
+    pub state: State<i64>,
+    pub stopped_at: Option<usize>,
+}
+
+// p / q, with q > 0.
+type Ratio = (i128, i128);
+
+fn ratio(p: i128, q: i128) -> Ratio {
+    if q < 0 {
+        (-p, -q)
+    } else {
+        (p, q)
+    }
+}
+
+// Whether a < b. This runs Euclid's algorithm on both at once rather than cross-multiplying, which
+// could overflow.
+fn less(a: Ratio, b: Ratio) -> bool {
+    let (qa, qb) = (a.0.div_euclid(a.1), b.0.div_euclid(b.1));
+    if qa != qb {
+        return qa < qb;
+    }
+    match (a.0.rem_euclid(a.1), b.0.rem_euclid(b.1)) {
+        (_, 0) => false,
+        (0, _) => true,
+        // a - q = ra / a.1 < rb / b.1 = b - q exactly when b.1 / rb < a.1 / ra
+        (ra, rb) => less((b.1, rb), (a.1, ra)),
+    }
+}
+
+// The open range of k for which `from + k * w` (w nonzero) is within half a cell of `cell`.
+fn strip(from: i64, w: i128, cell: i64) -> (Ratio, Ratio) {
+    let offset = 2 * (cell as i128 - from as i128);
+    let (a, b) = (ratio(offset - 1, 2 * w), ratio(offset + 1, 2 * w));
+    if less(b, a) {
+        (b, a)
+    } else {
+        (a, b)
+    }
+}
+
+// The first k in 0..=n at which `from + k * w` is inside `cell`, counting a cell as the open unit
+// square round its point, or None if it never is.
+fn entry(from: Vector<i64>, w: Vector<i128>, n: i128, cell: Vector<i64>) -> Option<Ratio> {
+    let (mut lo, mut hi) = ((0, 1), (n, 1));
+    for axis in 0..2 {
+        if w[axis] == 0 {
+            if cell[axis] != from[axis] {
+                return None;
+            }
+            continue;
+        }
+        let (a, b) = strip(from[axis], w[axis], cell[axis]);
+        if less(lo, a) {
+            lo = a;
+        }
+        if less(b, hi) {
+            hi = b;
+        }
+    }
+    less(lo, hi).then_some(lo)
+}
+
+// Where a move of n steps of `w` from `from` first runs into a rock, if it does: how many whole
+// steps it gets before it, and the rock. Only the columns of the map it crosses are looked at, and
+// in each only the rows it crosses, so this takes no longer for a long move than a short one.
+// Moving only along the edge of a rock's square, or through its corner, doesn't count.
+fn first_rock(
+    map: &ObstacleMap,
+    from: Vector<i64>,
+    w: Vector<i128>,
+    n: i128,
+) -> Option<(i128, Vector<i64>)> {
+    let (top, bottom) = (map.origin[1], map.origin[1] - map.height as i64 + 1);
+    let mut first: Option<(Ratio, Vector<i64>)> = None;
+    for x in map.origin[0]..map.origin[0] + map.width as i64 {
+        let (k_lo, k_hi) = if w[0] == 0 {
+            if x != from[0] {
+                continue;
+            }
+            (0, n)
+        } else {
+            let (a, b) = strip(from[0], w[0], x);
+            (a.0.div_euclid(a.1).max(0), (b.0.div_euclid(b.1) + 1).min(n))
+        };
+        if k_lo > k_hi {
+            continue;
+        }
+        let ends = [from[1] as i128 + k_lo * w[1], from[1] as i128 + k_hi * w[1]];
+        let lo = ends[0].min(ends[1]).max(bottom as i128) as i64;
+        let hi = ends[0].max(ends[1]).min(top as i128) as i64;
+        for y in lo..=hi {
+            if !map.is_blocked([x, y]) {
+                continue;
+            }
+            if let Some(at) = entry(from, w, n, [x, y]) {
+                if first.is_none_or(|(before, _)| less(at, before)) {
+                    first = Some((at, [x, y]));
+                }
+            }
+        }
+    }
+    // never a whole number of steps, as the ship's points are the middles of cells
+    first.map(|(at, rock)| (at.0.div_euclid(at.1), rock))
+}
+
+// Every move goes in a straight line, and runs into a rock if it passes through any part of the
+// rock's cell but its edges (so a ship can slip diagonally between two rocks touching at a
+// corner). A move that does is cut short at the last point on its line the ship could have
+// reached without touching the rock. Only quarter turns in the plane are supported, and it's an
+// error for anything to overflow an i64.
+pub fn sail(
+    map: &ObstacleMap,
+    moves: &[Move],
+    mode: Mode,
+    policy: Policy,
+) -> Result<Voyage, String> {
+    let mut state = mode.start();
+    if map.is_blocked(state.position) {
+        return Err(format!("the ship starts on a rock at {:?}", state.position));
+    }
+    for (i, mov) in moves.iter().enumerate() {
+        let transform = Transform::of_move(mov, mode)
+            .map_err(|why| format!("move {}: {:?} {}", i + 1, mov, why))?;
+        let overflow = || format!("move {}: {:?} overflows i64", i + 1, mov);
+        let hop = match (mode, *mov) {
+            (_, Move::F(amt)) => Some((state.vector, amt)),
+            (Mode::Ship, Move::N(amt)) => Some(([0, 1], amt)),
+            (Mode::Ship, Move::S(amt)) => Some(([0, -1], amt)),
+            (Mode::Ship, Move::E(amt)) => Some(([1, 0], amt)),
+            (Mode::Ship, Move::W(amt)) => Some(([-1, 0], amt)),
+            // turns, and moving the waypoint about
+            _ => None,
+        };
+        let ([dx, dy], amt) = match hop {
+            Some(hop) => hop,
+            None => {
+                state = transform.checked_apply(&state).ok_or_else(overflow)?;
+                continue;
+            }
+        };
+        // the move is n steps of w, the shortest step between points on its line
+        let g = gcd(&dx.unsigned_abs(), &dy.unsigned_abs()) as i128;
+        if g == 0 || amt == 0 {
+            continue;
+        }
+        let sign = amt.signum() as i128;
+        let w = [dx as i128 / g * sign, dy as i128 / g * sign];
+        let n = amt.unsigned_abs() as i128 * g;
+        let position = state.position;
+        // as far as it goes before leaving i64
+        let fits = (0..2)
+            .filter(|&axis| w[axis] != 0)
+            .map(|axis| {
+                let room = if w[axis] > 0 {
+                    i64::MAX as i128 - position[axis] as i128
+                } else {
+                    position[axis] as i128 - i64::MIN as i128
+                };
+                room / w[axis].abs()
+            })
+            .fold(n, i128::min);
+        let reach = |k: i128| {
+            [
+                (position[0] as i128 + k * w[0]) as i64,
+                (position[1] as i128 + k * w[1]) as i64,
+            ]
+        };
+        match first_rock(map, position, w, fits) {
+            Some((k, rock)) => match policy {
+                Policy::Stop => {
+                    state.position = reach(k);
+                    return Ok(Voyage {
+                        state,
+                        stopped_at: Some(i),
+                    });
+                }
+                Policy::Clip => state.position = reach(k),
+                Policy::Error => {
+                    return Err(format!(
+                        "move {}: {:?} runs onto the rock at {:?}",
+                        i + 1,
+                        mov,
+                        rock
+                    ))
+                }
+            },
+            None if fits < n => return Err(overflow()),
+            None => state.position = reach(n),
+        }
+    }
+    Ok(Voyage {
+        state,
+        stopped_at: None,
+    })
+}
+
+// Fewest ship-mode moves (N, S, E and W; the heading never needs to change) from `from` to `to`
+// that never touch a rock, or None if there aren't any. A leg longer than i32::MAX comes out as
+// several moves the same way.
+pub fn shortest_route(map: &ObstacleMap, from: Vector<i64>, to: Vector<i64>) -> Option<Vec<Move>> {
+    if map.is_blocked(from) || map.is_blocked(to) {
+        return None;
+    }
+    // A route only ever needs to turn on a column (row) that crosses the map, the one just either
+    // side of it, or the start's or the end's: any other is open water all the way along, so a
+    // route turning there can turn on the nearest of those instead. Searching just those keeps
+    // the work down to the size of the map however far apart the ends are.
+    let axis = |i: usize, first: i64, last: i64| {
+        let mut coords: Vec<i64> = (first - 1..=last + 1).chain(vec![from[i], to[i]]).collect();
+        coords.sort_unstable();
+        coords.dedup();
+        coords
+    };
+    let xs = axis(0, map.origin[0], map.origin[0] + map.width as i64 - 1);
+    let ys = axis(1, map.origin[1] - map.height as i64 + 1, map.origin[1]);
+    let width = xs.len();
+    let node = |[x, y]: Vector<i64>| (xs.binary_search(&x).unwrap(), ys.binary_search(&y).unwrap());
+    let (start, end) = (node(from), node(to));
+    let directions = [
+        (0, 1, Move::N as fn(i32) -> Move),
+        (0, -1, Move::S),
+        (1, 0, Move::E),
+        (-1, 0, Move::W),
+    ];
+    // the node each one was first reached from, and which way
+    let mut came_from: Vec<Option<((usize, usize), usize)>> = vec![None; width * ys.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(at) = queue.pop_front() {
+        if at == end {
+            let mut route = vec![];
+            let mut back = end;
+            while back != start {
+                let (previous, way) = came_from[back.1 * width + back.0].unwrap();
+                let mut distance =
+                    (xs[back.0] - xs[previous.0]).abs() + (ys[back.1] - ys[previous.1]).abs();
+                while distance > 0 {
+                    let amt = distance.min(i32::MAX as i64);
+                    route.push(directions[way].2(amt as i32));
+                    distance -= amt;
+                }
+                back = previous;
+            }
+            route.reverse();
+            return Some(route);
+        }
+        for (way, (di, dj, _)) in directions.iter().enumerate() {
+            let mut next = at;
+            // the columns (rows) skipped between neighbouring nodes are all off the map
+            while let (Some(i), Some(j)) = (
+                next.0.checked_add_signed(*di),
+                next.1.checked_add_signed(*dj),
+            ) {
+                if i >= width || j >= ys.len() || map.is_blocked([xs[i], ys[j]]) {
+                    break;
+                }
+                next = (i, j);
+                if next != start && came_from[j * width + i].is_none() {
+                    came_from[j * width + i] = Some((at, way));
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12::{parse_input, simulate};
+    use Move::*;
+
+    static HARBOUR: &str = "
+.....
+.###.
+.....";
+
+    #[test]
+    fn obstacle_policies() {
+        // the ship starts at (0, 0), just west of the rocks, heading east into them
+        let map = ObstacleMap::parse(HARBOUR).unwrap().with_origin([0, 1]);
+        assert!(map.is_blocked([1, 0]));
+        assert!(!map.is_blocked([0, 0]));
+        assert!(!map.is_blocked([100, 0]));
+        let moves = parse_input("F3\nS1\nE2\nN5");
+        assert_eq!(
+            Ok(Voyage {
+                state: State {
+                    position: [0, 0],
+                    vector: [1, 0]
+                },
+                stopped_at: Some(0)
+            }),
+            sail(&map, &moves, Mode::Ship, Policy::Stop)
+        );
+        assert_eq!(
+            Ok(Voyage {
+                state: State {
+                    position: [2, -1],
+                    vector: [1, 0]
+                },
+                stopped_at: None
+            }),
+            sail(&map, &moves, Mode::Ship, Policy::Clip)
+        );
+        // a route that misses the rocks gets all the way with either
+        let clear = parse_input("S1\nF4");
+        assert_eq!(
+            sail(&map, &clear, Mode::Ship, Policy::Clip),
+            sail(&map, &clear, Mode::Ship, Policy::Stop)
+        );
+        assert_eq!(
+            None,
+            sail(&map, &clear, Mode::Ship, Policy::Stop)
+                .unwrap()
+                .stopped_at
+        );
+        assert_eq!(
+            Err("move 1: F(3) runs onto the rock at [1, 0]".to_string()),
+            sail(&map, &moves, Mode::Ship, Policy::Error)
+        );
+        // a waypoint-mode move is stopped by a rock anywhere along it, not just at its end
+        let moves = parse_input("S1\nF1");
+        assert_eq!(
+            Err("move 2: F(1) runs onto the rock at [1, 0]".to_string()),
+            sail(&map, &moves, Mode::Waypoint, Policy::Error)
+        );
+        assert_eq!(
+            Ok([0, 0]),
+            sail(&map, &moves, Mode::Waypoint, Policy::Clip).map(|voyage| voyage.state.position)
+        );
+        // cut short at the last point on its line before the rock, here halfway along
+        let moves = parse_input("W10\nN1\nF1\nE2\nS3\nF2");
+        assert_eq!(
+            Ok([2, 1]),
+            sail(&map, &moves, Mode::Waypoint, Policy::Clip).map(|voyage| voyage.state.position)
+        );
+        assert_eq!(
+            Err("move 6: F(2) runs onto the rock at [3, 0]".to_string()),
+            sail(&map, &moves, Mode::Waypoint, Policy::Error)
+        );
+        // slipping diagonally between rocks that only touch at a corner
+        let checks = ObstacleMap::parse(".#\n#.").unwrap().with_origin([0, 2]);
+        let moves = parse_input("W9\nF2");
+        assert_eq!(
+            Ok([2, 2]),
+            sail(&checks, &moves, Mode::Waypoint, Policy::Error)
+                .map(|voyage| voyage.state.position)
+        );
+        // a long move takes no longer than a short one
+        let moves = parse_input("N10\nF2000000000");
+        assert_eq!(
+            simulate(&moves, Mode::Waypoint, Mode::Waypoint.start()).ok(),
+            sail(&map, &moves, Mode::Waypoint, Policy::Error)
+                .ok()
+                .map(|voyage| voyage.state)
+        );
+        let moves = parse_input("N2000000000\nF2000000000\nF2000000000\nF2000000000");
+        assert_eq!(
+            Err("move 4: F(2000000000) overflows i64".to_string()),
+            sail(&map, &moves, Mode::Waypoint, Policy::Clip)
+        );
+        assert!(sail(&map, &[L(45)], Mode::Ship, Policy::Clip).is_err());
+        let map = ObstacleMap::parse("#").unwrap();
+        assert!(sail(&map, &[], Mode::Ship, Policy::Clip).is_err());
+    }
+
+    #[test]
+    fn routes_round_rocks() {
+        let map = ObstacleMap::parse(HARBOUR).unwrap();
+        // from the middle of the west side of the rocks to the east side
+        let route = shortest_route(&map, [0, -1], [4, -1]).unwrap();
+        assert_eq!(3, route.len());
+        let mut ship = Mode::Ship.start();
+        ship.position = [0, -1];
+        assert_eq!(
+            Ok([4, -1]),
+            simulate(&route, Mode::Ship, ship).map(|end| end.position)
+        );
+        assert_eq!(Some(vec![]), shortest_route(&map, [2, 0], [2, 0]));
+        assert_eq!(Some(vec![W(12)]), shortest_route(&map, [10, 5], [-2, 5]));
+        assert_eq!(None, shortest_route(&map, [2, -1], [0, 0]));
+
+        let walled = ObstacleMap::parse("###\n#.#\n###").unwrap();
+        assert_eq!(None, shortest_route(&walled, [1, -1], [5, 5]));
+        // in through the gap at the bottom
+        let gap = ObstacleMap::parse("#####\n#...#\n##.##").unwrap();
+        assert_eq!(
+            Some(vec![N(2), E(1)]),
+            shortest_route(&gap, [2, -3], [3, -1])
+        );
+
+        // far from the map, the search stays the size of the map
+        let far = |from: Vector<i64>, to: Vector<i64>| {
+            let route = shortest_route(&map, from, to).unwrap();
+            let mut ship = Mode::Ship.start();
+            ship.position = from;
+            assert_eq!(
+                Ok(to),
+                simulate(&route, Mode::Ship, ship).map(|end| end.position)
+            );
+            route
+        };
+        assert_eq!(2, far([0, -1], [100_000, 100_000]).len());
+        // round the rocks, with the long leg east split into i32-sized moves
+        let route = far([-(1 << 40), -1], [1 << 40, -1]);
+        assert_eq!(2 + 1025, route.len());
+    }
+}
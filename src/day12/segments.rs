@@ -0,0 +1,225 @@
+use super::{Mode, Move, Scalar, State, Transform, Unsupported};
+use std::ops::Range;
+
+// Segment tree over the transforms of a list of moves: leaves are single moves (padded out to a
+// power of two with identities) and every inner node is its left child followed by its right
+// child. Any run of moves composes in O(log n), and swapping a move out touches O(log n) nodes.
+// None of the arithmetic is checked for overflow: with integers that's a panic in debug builds and
+// wrapping in release, so use a `T` wide enough for the route.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformTree<T> {
+    mode: Mode,
+    len: usize,
+    leaves: usize,
+    nodes: Vec<Transform<T>>,
+}
+
+impl<T: Scalar> TransformTree<T> {
+    // Err if `Transform::of_move` can't do some move in `T`.
+    pub fn new(moves: &[Move], mode: Mode) -> Result<TransformTree<T>, Unsupported> {
+        let leaves = moves.len().next_power_of_two();
+        let mut nodes = vec![Transform::identity(); 2 * leaves];
+        for (i, mov) in moves.iter().enumerate() {
+            nodes[leaves + i] = Transform::of_move(mov, mode)?;
+        }
+        for node in (1..leaves).rev() {
+            nodes[node] = nodes[2 * node].then(&nodes[2 * node + 1]);
+        }
+        Ok(TransformTree {
+            mode,
+            len: moves.len(),
+            leaves,
+            nodes,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Moves `range` one after the other, as a single transform.
+    pub fn range(&self, range: Range<usize>) -> Transform<T> {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "{:?} out of bounds for {} moves",
+            range,
+            self.len
+        );
+        let (mut left, mut right) = (Transform::identity(), Transform::identity());
+        let (mut lo, mut hi) = (range.start + self.leaves, range.end + self.leaves);
+        while lo < hi {
+            if lo % 2 == 1 {
+                left = left.then(&self.nodes[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                right = self.nodes[hi].then(&right);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        left.then(&right)
+    }
+
+    pub fn total(&self) -> Transform<T> {
+        self.nodes[1]
+    }
+
+    pub fn state_after(&self, range: Range<usize>, start: &State<T>) -> State<T> {
+        self.range(range).apply(start)
+    }
+
+    // Where all the moves lead from `start` if move `k` were `mov` instead, leaving the tree as
+    // it is. Err if `T` can't do `mov`.
+    pub fn final_state_with(
+        &self,
+        k: usize,
+        mov: &Move,
+        start: &State<T>,
+    ) -> Result<State<T>, Unsupported> {
+        let replaced = Transform::of_move(mov, self.mode)?;
+        let transform = self
+            .range(0..k)
+            .then(&replaced)
+            .then(&self.range(k + 1..self.len));
+        Ok(transform.apply(start))
+    }
+
+    // Swaps move `k` for `mov` for good. Err (and nothing changed) if `T` can't do `mov`.
+    pub fn replace(&mut self, k: usize, mov: &Move) -> Result<(), Unsupported> {
+        assert!(k < self.len, "move {} out of bounds for {}", k, self.len);
+        let mut node = k + self.leaves;
+        self.nodes[node] = Transform::of_move(mov, self.mode)?;
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node].then(&self.nodes[2 * node + 1]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use crate::day12::{parse_input, simulate};
+    use test::Bencher;
+
+    fn generated_moves(count: usize, seed: u64) -> Vec<Move> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let amt = (state >> 40) as i32 % 100;
+                match state >> 61 {
+                    0 => Move::N(amt),
+                    1 => Move::S(amt),
+                    2 => Move::E(amt),
+                    3 => Move::W(amt),
+                    4 => Move::L(90 * (amt % 4)),
+                    5 => Move::R(90 * (amt % 4)),
+                    _ => Move::F(amt),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ranges_match_simulation() {
+        for (seed, count) in [0, 1, 2, 7, 64, 100].iter().enumerate() {
+            let moves = generated_moves(*count, seed as u64);
+            for mode in [Mode::Ship, Mode::Waypoint].iter() {
+                let tree = TransformTree::<i64>::new(&moves, *mode).unwrap();
+                let start = State {
+                    position: [5, -3],
+                    vector: [2, 7],
+                };
+                assert_eq!(
+                    simulate(&moves, *mode, start),
+                    Ok(tree.total().apply(&start))
+                );
+                for i in 0..=moves.len() {
+                    for j in (i..=moves.len()).step_by(3) {
+                        assert_eq!(
+                            simulate(&moves[i..j], *mode, start),
+                            Ok(tree.state_after(i..j, &start))
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn replaced_moves() {
+        let mut moves = generated_moves(50, 9);
+        let start = Mode::Waypoint.start::<i64>();
+        let mut tree = TransformTree::new(&moves, Mode::Waypoint).unwrap();
+        for (k, mov) in generated_moves(50, 10).iter().enumerate() {
+            let mut changed = moves.clone();
+            changed[k] = *mov;
+            let expected = simulate(&changed, Mode::Waypoint, start);
+            assert_eq!(expected, tree.final_state_with(k, mov, &start));
+            assert_eq!(
+                simulate(&moves, Mode::Waypoint, start),
+                Ok(tree.total().apply(&start))
+            );
+            tree.replace(k, mov).unwrap();
+            moves = changed;
+            assert_eq!(expected, Ok(tree.total().apply(&start)));
+        }
+        assert_eq!(
+            Err(Unsupported::InexactTurn),
+            tree.final_state_with(3, &Move::L(45), &start)
+        );
+        assert_eq!(Err(Unsupported::ThreeD), tree.replace(3, &Move::U(1)));
+        assert_eq!(
+            simulate(&moves, Mode::Waypoint, start),
+            Ok(tree.total().apply(&start))
+        );
+    }
+
+    fn real_moves() -> Vec<Move> {
+        parse_input(
+            &std::fs::read_to_string("src/day12/input.in").expect("failed to read day12 input"),
+        )
+    }
+
+    // Every single-move what-if on the real input, each one simulated from scratch...
+    #[bench]
+    fn bench_what_ifs_resimulated(b: &mut Bencher) {
+        let moves = real_moves();
+        b.iter(|| {
+            let mut changed = moves.clone();
+            (0..moves.len())
+                .map(|k| {
+                    changed[k] = Move::F(1);
+                    let end = simulate(&changed, Mode::Waypoint, Mode::Waypoint.start::<i64>());
+                    changed[k] = moves[k];
+                    end
+                })
+                .collect::<Vec<_>>()
+        });
+    }
+
+    // ...and answered by the tree.
+    #[bench]
+    fn bench_what_ifs_tree(b: &mut Bencher) {
+        let moves = real_moves();
+        b.iter(|| {
+            let tree = TransformTree::new(&moves, Mode::Waypoint).unwrap();
+            let start = Mode::Waypoint.start::<i64>();
+            (0..moves.len())
+                .map(|k| tree.final_state_with(k, &Move::F(1), &start))
+                .collect::<Vec<_>>()
+        });
+    }
+}
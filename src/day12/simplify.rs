@@ -0,0 +1,539 @@
+use super::{simulate, simulate_checked, Mode, Move, State, Vector};
+use crate::day13::number_theory::{extended_gcd, gcd};
+use std::convert::TryFrom;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Form {
+    // as few moves as possible (see `simplify` for when waypoint mode can miss)
+    Shortest,
+    // ship mode: N or S, then E or W, then R to the final heading. Waypoint mode: N/S and E/W to
+    // put the waypoint on the ship's final position, F1, then N/S and E/W to its final place.
+    // Moves that would do nothing are left out.
+    Canonical,
+}
+
+fn amount(value: i128) -> Result<i32, String> {
+    i32::try_from(value).map_err(|_| format!("{} is too large for a single move", value))
+}
+
+// How many moves it takes to go `value` along an axis (or forward), i32::MAX at most at a time.
+fn pieces(value: i128) -> u64 {
+    value.unsigned_abs().div_ceil(i32::MAX as u128) as u64
+}
+
+// `value` in as few moves as `pieces` says, `forward` ones if it's positive and `back` ones if not.
+fn split(value: i128, forward: fn(i32) -> Move, back: fn(i32) -> Move) -> Vec<Move> {
+    let mov = if value < 0 { back } else { forward };
+    let mut left = value.unsigned_abs();
+    let mut moves = vec![];
+    while left > 0 {
+        let amt = left.min(i32::MAX as u128);
+        moves.push(mov(amt as i32));
+        left -= amt;
+    }
+    moves
+}
+
+fn difference(from: Vector<i64>, to: Vector<i64>) -> Vector<i128> {
+    [
+        to[0] as i128 - from[0] as i128,
+        to[1] as i128 - from[1] as i128,
+    ]
+}
+
+// Moves by `offset`, north-south first.
+fn translation(offset: Vector<i128>) -> Vec<Move> {
+    let mut moves = split(offset[1], Move::N, Move::S);
+    moves.extend(split(offset[0], Move::E, Move::W));
+    moves
+}
+
+// `translation` in at most one move along each axis, or Err.
+fn single_translation(offset: Vector<i128>) -> Result<Vec<Move>, String> {
+    amount(offset[0])?;
+    amount(offset[1])?;
+    Ok(translation(offset))
+}
+
+// None if it overflows.
+fn clockwise(v: Vector<i64>, quarters: usize) -> Option<Vector<i64>> {
+    (0..quarters).try_fold(v, |[x, y], _| Some([y, x.checked_neg()?]))
+}
+
+fn turn(quarters: usize) -> Option<Move> {
+    match quarters % 4 {
+        0 => None,
+        q => Some(Move::R(90 * q as i32)),
+    }
+}
+
+// The cheapest way to take the waypoint from `from` to `to`: turn it clockwise by some quarters,
+// then translate it by an offset. Also how many moves that is.
+fn retarget_plan(from: Vector<i64>, to: Vector<i64>) -> (usize, Vector<i128>, u64) {
+    (0..4)
+        .filter_map(|quarters| {
+            let offset = difference(clockwise(from, quarters)?, to);
+            let cost = (quarters != 0) as u64 + pieces(offset[0]) + pieces(offset[1]);
+            Some((quarters, offset, cost))
+        })
+        .min_by_key(|(_, _, cost)| *cost)
+        .expect("not turning at all never overflows")
+}
+
+fn retarget(from: Vector<i64>, to: Vector<i64>) -> Vec<Move> {
+    let (quarters, offset, _) = retarget_plan(from, to);
+    turn(quarters)
+        .into_iter()
+        .chain(translation(offset))
+        .collect()
+}
+
+// A forward leg: F `k` times (in total) with the waypoint at this.
+type Leg = (Vector<i64>, i128);
+
+fn legs_cost(start: Vector<i64>, legs: &[Leg], end: Vector<i64>) -> u64 {
+    let mut waypoint = start;
+    let mut cost = 0;
+    for (leg, k) in legs {
+        cost += retarget_plan(waypoint, *leg).2 + pieces(*k);
+        waypoint = *leg;
+    }
+    cost + retarget_plan(waypoint, end).2
+}
+
+fn legs_route(start: Vector<i64>, legs: &[Leg], end: Vector<i64>) -> Vec<Move> {
+    let mut route = vec![];
+    let mut waypoint = start;
+    for (leg, k) in legs {
+        route.extend(retarget(waypoint, *leg));
+        route.extend(split(*k, Move::F, |amt| Move::F(-amt)));
+        waypoint = *leg;
+    }
+    route.extend(retarget(waypoint, end));
+    route
+}
+
+fn divisors(n: u64) -> Vec<u64> {
+    let mut small = vec![];
+    let mut large = vec![];
+    let mut d = 1;
+    while d * d <= n {
+        if n.is_multiple_of(d) {
+            small.push(d);
+            if d * d != n {
+                large.push(n / d);
+            }
+        }
+        d += 1;
+    }
+    small.extend(large.into_iter().rev());
+    small
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+// Some of the k1 x + k2 y = z with k1, k2 >= 1, for non-zero x and y: the one with the smallest
+// k1, the one with the smallest k2 and the ones where neither is much bigger than the other. Of
+// all the solutions, one of those has the fewest pieces.
+fn positive_solutions(x: i64, y: i64, z: i64) -> Vec<(i128, i128)> {
+    if x == i64::MIN || y == i64::MIN {
+        return vec![];
+    }
+    let (g, s, t) = extended_gcd(x, y);
+    let (g, z) = (g as i128, z as i128);
+    if z % g != 0 {
+        return vec![];
+    }
+    // every solution is (k1 + j dy, k2 - j dx) for some j
+    let (k1, k2) = (s as i128 * (z / g), t as i128 * (z / g));
+    let (dy, dx) = (y as i128 / g, x as i128 / g);
+    let (mut lo, mut hi) = (i128::MIN, i128::MAX);
+    // c j >= r for both
+    for (c, r) in [(dy, 1 - k1), (-dx, 1 - k2)].iter() {
+        if *c > 0 {
+            lo = lo.max(-floor_div(-r, *c));
+        } else {
+            hi = hi.min(floor_div(*r, *c));
+        }
+    }
+    if lo > hi {
+        return vec![];
+    }
+    let mut js = vec![lo, hi];
+    if dy + dx != 0 {
+        let even = floor_div(k2 - k1, dy + dx);
+        js.extend([even, even + 1].iter().map(|j| (*j).clamp(lo, hi)));
+    }
+    js.into_iter()
+        .filter(|j| *j != i128::MIN && *j != i128::MAX)
+        .map(|j| (k1 + j * dy, k2 - j * dx))
+        .collect()
+}
+
+// k1 a + k2 b = p with k1, k2 >= 1, in as many ways as `positive_solutions` gives.
+fn multipliers(a: Vector<i64>, b: Vector<i64>, p: Vector<i64>) -> Vec<(i128, i128)> {
+    let cross = |u: Vector<i64>, v: Vector<i64>| {
+        (u[0] as i128 * v[1] as i128).checked_sub(u[1] as i128 * v[0] as i128)
+    };
+    let (det, n1, n2) = match (cross(a, b), cross(p, b), cross(a, p)) {
+        (Some(det), Some(n1), Some(n2)) => (det, n1, n2),
+        _ => return vec![],
+    };
+    if det != 0 {
+        return match (n1 % det, n2 % det, n1 / det, n2 / det) {
+            (0, 0, k1, k2) if k1 >= 1 && k2 >= 1 => vec![(k1, k2)],
+            _ => vec![],
+        };
+    }
+    // a and b are parallel, so p has to be too, and then one axis says it all
+    match (0..2).find(|axis| a[*axis] != 0) {
+        Some(axis) if b != [0, 0] && n2 == 0 => positive_solutions(a[axis], b[axis], p[axis]),
+        _ => vec![],
+    }
+}
+
+// The route's own forward legs, with legs flown with the same waypoint back to back merged into
+// one.
+fn squeezed(moves: &[Move], start: State<i64>) -> Result<Vec<Leg>, String> {
+    let mut legs: Vec<Leg> = vec![];
+    let mut state = start;
+    for mov in moves {
+        if let Move::F(amt) = mov {
+            if *amt != 0 && state.vector != [0, 0] {
+                match legs.last_mut() {
+                    Some((waypoint, total)) if *waypoint == state.vector => *total += *amt as i128,
+                    _ => legs.push((state.vector, *amt as i128)),
+                }
+            }
+        }
+        // the same steps `simulate_checked` already took, so nothing overflows
+        state =
+            simulate(&[*mov], Mode::Waypoint, state).map_err(|why| format!("{:?} {}", mov, why))?;
+    }
+    Ok(legs)
+}
+
+// Two legs, the first with the waypoint anywhere along the row (`axis` 0) or column (`axis` 1)
+// that `start` and `end` share, then `end` itself; and the same the other way round.
+fn line_plans(start: Vector<i64>, end: State<i64>, axis: usize) -> Vec<Vec<Leg>> {
+    let (e, p) = (end.vector, end.position);
+    let other = 1 - axis;
+    let shared = start[other] as i128;
+    // the starting waypoint, (10, 1), has no zero to share
+    if shared == 0 || p[other] as i128 % shared != 0 {
+        return vec![];
+    }
+    // k1 + k2 legs, all the same distance along `other`
+    let n = p[other] as i128 / shared;
+    let mut plans = vec![];
+    for (fixed, fixed_first) in [(e, false), (start, true)].iter() {
+        // the free waypoint is `fixed` + rest / k along `axis`, with k its own number of legs
+        let rest = p[axis] as i128 - n * fixed[axis] as i128;
+        // past this, the free waypoint's leg or the move onto it needs splitting
+        let most = i32::MAX as i128 * i32::MAX as i128;
+        if rest == 0 || rest.abs() > most {
+            continue;
+        }
+        for k in divisors(rest.unsigned_abs() as u64) {
+            let k = k as i128;
+            let along = match i64::try_from(fixed[axis] as i128 + rest / k) {
+                Ok(along) if k < n => along,
+                _ => continue,
+            };
+            let mut free = *fixed;
+            free[axis] = along;
+            plans.push(if *fixed_first {
+                vec![(*fixed, n - k), (free, k)]
+            } else {
+                vec![(free, k), (*fixed, n - k)]
+            });
+        }
+    }
+    plans
+}
+
+// Every one and two leg route (see `simplify`) that could be the shortest.
+fn waypoint_plans(start: Vector<i64>, end: State<i64>) -> Vec<Vec<Leg>> {
+    let [x, y] = end.position;
+    if [x, y] == [0, 0] {
+        return vec![vec![]];
+    }
+    let mut plans = vec![];
+    // one leg of k times a waypoint that divides the whole trip
+    for k in divisors(gcd(&x.unsigned_abs(), &y.unsigned_abs())) {
+        let k = k as i128;
+        let leg = [(x as i128 / k) as i64, (y as i128 / k) as i64];
+        plans.push(vec![(leg, k)]);
+    }
+    // two legs, with one move or two between them and none before or after; or one move each
+    // between, and before or after, via a waypoint one move from both the start and the end
+    let e = end.vector;
+    let mut between = vec![[e[0], start[1]], [start[0], e[1]]];
+    between.extend((1..4).filter_map(|quarters| clockwise(start, quarters)));
+    between.extend((1..4).filter_map(|quarters| clockwise(e, quarters)));
+    let mut pairs = vec![(start, e)];
+    for waypoint in between {
+        pairs.push((waypoint, e));
+        pairs.push((start, waypoint));
+    }
+    for (a, b) in pairs {
+        for (k1, k2) in multipliers(a, b, end.position) {
+            plans.push(vec![(a, k1), (b, k2)]);
+        }
+    }
+    // ...where a whole row or column is one move from both
+    for axis in 0..2 {
+        if start[1 - axis] == e[1 - axis] {
+            plans.extend(line_plans(start, end, axis));
+        }
+    }
+    plans
+}
+
+fn heading_quarters(heading: Vector<i64>) -> usize {
+    (0..4)
+        .find(|quarters| clockwise([1, 0], *quarters) == Some(heading))
+        .expect("ship mode headings are unit vectors")
+}
+
+// An equivalent route, one that ends in the same state from `mode`'s starting state. Only
+// quarter turns are supported, and the result is checked by simulating both.
+//
+// `Form::Shortest` in waypoint mode: a route is forward legs with the waypoint moved about
+// before, between and after them. Getting the waypoint anywhere takes at most two moves (and one
+// only when it's a turn or a move along one axis away), so a single leg never needs more than
+// five moves, while three or more legs need at least five. Two legs can only win with four
+// moves or fewer, which leaves a few shapes: the first leg with the starting waypoint and the
+// last with the final one, or a waypoint one move from both of those in place of either. Each
+// of those, and every single leg, is solved for exactly. That argument counts each move once,
+// so it only holds while amounts fit in an i32: bigger ones are split into several moves, and
+// then a shorter route with more legs can be missed. The original route is kept if nothing
+// found beats it.
+pub fn simplify(moves: &[Move], mode: Mode, form: Form) -> Result<Vec<Move>, String> {
+    let start = mode.start();
+    let end = simulate_checked(moves, mode, start)?;
+    let position = difference([0, 0], end.position);
+    let simplified = match (mode, form) {
+        (Mode::Ship, Form::Shortest) => {
+            let mut route = translation(position);
+            route.extend(turn(heading_quarters(end.vector)));
+            route
+        }
+        (Mode::Ship, Form::Canonical) => {
+            let mut route = single_translation(position)?;
+            route.extend(turn(heading_quarters(end.vector)));
+            route
+        }
+        (Mode::Waypoint, Form::Canonical) if end.position == [0, 0] => {
+            single_translation(difference(start.vector, end.vector))?
+        }
+        (Mode::Waypoint, Form::Canonical) => {
+            let mut route = single_translation(difference(start.vector, end.position))?;
+            route.push(Move::F(1));
+            route.extend(single_translation(difference(end.position, end.vector))?);
+            route
+        }
+        (Mode::Waypoint, Form::Shortest) => {
+            let mut plans = waypoint_plans(start.vector, end);
+            plans.push(squeezed(moves, start)?);
+            let best = plans
+                .iter()
+                .min_by_key(|legs| legs_cost(start.vector, legs, end.vector))
+                .unwrap();
+            if legs_cost(start.vector, best, end.vector) < moves.len() as u64 {
+                legs_route(start.vector, best, end.vector)
+            } else {
+                moves.to_vec()
+            }
+        }
+    };
+    if simulate_checked(&simplified, mode, start) != Ok(end) {
+        return Err(format!(
+            "{:?} doesn't end where the original route does",
+            simplified
+        ));
+    }
+    Ok(simplified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12::parse_input;
+    use Move::*;
+
+    fn real_moves() -> Vec<Move> {
+        parse_input(
+            &std::fs::read_to_string("src/day12/input.in").expect("failed to read day12 input"),
+        )
+    }
+
+    #[test]
+    fn simplify_ship_routes() {
+        let sample = parse_input("F10\nN3\nF7\nR90\nF11");
+        assert_eq!(
+            Ok(vec![S(8), E(17), R(90)]),
+            simplify(&sample, Mode::Ship, Form::Shortest)
+        );
+        assert_eq!(
+            Ok(vec![]),
+            simplify(
+                &[L(90), R(90), N(3), S(3), F(0)],
+                Mode::Ship,
+                Form::Shortest
+            )
+        );
+        assert_eq!(
+            Ok(vec![N(1), W(2), R(180)]),
+            simplify(&[L(180), F(2), N(1)], Mode::Ship, Form::Canonical)
+        );
+        let simplified = simplify(&real_moves(), Mode::Ship, Form::Shortest).unwrap();
+        assert!(simplified.len() <= 3);
+        assert_eq!(
+            Err("move 1: L(45) isn't a quarter turn".to_string()),
+            simplify(&[L(45)], Mode::Ship, Form::Shortest)
+        );
+        assert_eq!(
+            Err("move 2: U(3) only works in 3D".to_string()),
+            simplify(&[F(1), U(3)], Mode::Ship, Form::Shortest)
+        );
+    }
+
+    #[test]
+    fn simplify_waypoint_routes() {
+        let sample = parse_input("F10\nN3\nF7\nR90\nF11");
+        let shortest = simplify(&sample, Mode::Waypoint, Form::Shortest).unwrap();
+        assert!(shortest.len() <= sample.len());
+        assert_eq!(
+            Ok(vec![F(10)]),
+            simplify(&[F(10)], Mode::Waypoint, Form::Shortest)
+        );
+        assert_eq!(
+            Ok(vec![R(90)]),
+            simplify(&[L(90), L(90), L(90)], Mode::Waypoint, Form::Shortest)
+        );
+        // the turns in between cancel out, so the two legs become one
+        assert_eq!(
+            Ok(vec![F(5), N(2)]),
+            simplify(
+                &[F(2), L(90), R(90), F(3), N(2)],
+                Mode::Waypoint,
+                Form::Shortest
+            )
+        );
+        assert_eq!(
+            Ok(vec![N(9), E(90), F(1), S(9), W(90)]),
+            simplify(&[F(10)], Mode::Waypoint, Form::Canonical)
+        );
+        assert_eq!(
+            Ok(vec![W(20)]),
+            simplify(
+                &[L(90), L(90), E(10), R(180), W(10)],
+                Mode::Waypoint,
+                Form::Canonical
+            )
+        );
+
+        // legs with different waypoints are kept as they are when nothing shorter turns up
+        let legs = parse_input("F3\nR90\nF2");
+        assert_eq!(
+            Ok(vec![F(3), R(90), F(2)]),
+            simplify(&legs, Mode::Waypoint, Form::Shortest)
+        );
+        assert_eq!(
+            5,
+            simplify(&legs, Mode::Waypoint, Form::Canonical)
+                .unwrap()
+                .len()
+        );
+        // and squeezed in between when something is
+        let legs = parse_input("F1\nN2\nS2\nL90\nR90\nF2\nE3\nF1\nL180\nL180\nF1\nN0");
+        assert_eq!(
+            Ok(vec![F(3), E(3), F(2)]),
+            simplify(&legs, Mode::Waypoint, Form::Shortest)
+        );
+
+        // amounts too big for one move get split, and the original route is kept if nothing beats it
+        assert_eq!(
+            Ok(vec![F(i32::MAX), F(1)]),
+            simplify(&[F(i32::MAX), F(1)], Mode::Waypoint, Form::Shortest)
+        );
+        let legs = [F(i32::MAX), N(5), F(i32::MAX)];
+        assert_eq!(
+            Ok(legs.to_vec()),
+            simplify(&legs, Mode::Waypoint, Form::Shortest)
+        );
+        assert_eq!(
+            Ok(vec![F(i32::MAX), F(i32::MAX), F(2), S(1), E(7)]),
+            simplify(
+                &[F(i32::MAX), F(i32::MAX), F(2), S(1), E(7)],
+                Mode::Waypoint,
+                Form::Shortest
+            )
+        );
+
+        let moves = real_moves();
+        let shortest = simplify(&moves, Mode::Waypoint, Form::Shortest).unwrap();
+        assert!(shortest.len() <= 7);
+        let canonical = simplify(&moves, Mode::Waypoint, Form::Canonical).unwrap();
+        assert!(canonical.len() <= 5);
+    }
+
+    #[test]
+    fn nothing_shorter_in_waypoint_mode() {
+        // every route of up to four moves with small amounts, against the shortest of them that
+        // ends up in the same state
+        let mut alphabet = vec![R(90), R(180), R(270)];
+        for amt in 1..=2 {
+            alphabet.extend(vec![N(amt), S(amt), E(amt), W(amt), F(amt)]);
+        }
+        let start = Mode::Waypoint.start::<i64>();
+        let mut routes = vec![vec![]];
+        let mut layer = vec![vec![]];
+        for _ in 0..4 {
+            layer = layer
+                .iter()
+                .flat_map(|route: &Vec<Move>| {
+                    alphabet.iter().map(move |mov| {
+                        let mut longer = route.clone();
+                        longer.push(*mov);
+                        longer
+                    })
+                })
+                .collect();
+            routes.extend(layer.iter().cloned());
+        }
+        let mut fewest = std::collections::HashMap::new();
+        for route in routes.iter() {
+            let end = simulate(route, Mode::Waypoint, start).unwrap();
+            let best = fewest
+                .entry((end.position, end.vector))
+                .or_insert(route.len());
+            *best = route.len().min(*best);
+        }
+        for route in routes.iter() {
+            let end = simulate(route, Mode::Waypoint, start).unwrap();
+            let fewest = fewest[&(end.position, end.vector)];
+            let simplified = simplify(route, Mode::Waypoint, Form::Shortest).unwrap();
+            assert!(
+                simplified.len() <= fewest,
+                "{:?} became {:?}",
+                route,
+                simplified
+            );
+            // and without the original route's own legs to fall back on
+            let searched = waypoint_plans(start.vector, end)
+                .iter()
+                .map(|legs| legs_cost(start.vector, legs, end.vector))
+                .min();
+            assert!(searched.unwrap() <= fewest as u64, "{:?}", route);
+        }
+    }
+}
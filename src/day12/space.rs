@@ -0,0 +1,358 @@
+use super::{Checked, Mode, Move, Scalar, Unsupported};
+
+pub type Vector3<T> = [T; 3];
+
+// x is east, y north and z up. In ship mode `vector` is the heading and `up` the way the ship's
+// deck faces, both unit vectors; in waypoint mode `vector` is the waypoint relative to the ship
+// and `up` stays put. Moves in the plane do exactly what they do in 2D and leave z at 0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct State3<T> {
+    pub position: Vector3<T>,
+    pub vector: Vector3<T>,
+    pub up: Vector3<T>,
+}
+
+pub fn start<T: Scalar>(mode: Mode) -> State3<T> {
+    let (zero, one) = (T::from_i32(0), T::from_i32(1));
+    State3 {
+        position: [zero; 3],
+        vector: match mode {
+            Mode::Ship => [one, zero, zero],
+            Mode::Waypoint => [T::from_i32(10), one, zero],
+        },
+        up: [zero, zero, one],
+    }
+}
+
+// The arithmetic a step is done in, where None means something overflowed.
+trait Arithmetic<T> {
+    fn add(a: T, b: T) -> Option<T>;
+    fn mul(a: T, b: T) -> Option<T>;
+}
+
+// never says None, so it wraps (or panics in debug builds) instead
+struct Plain;
+
+struct Checking;
+
+impl<T: Scalar> Arithmetic<T> for Plain {
+    fn add(a: T, b: T) -> Option<T> {
+        Some(a + b)
+    }
+
+    fn mul(a: T, b: T) -> Option<T> {
+        Some(a * b)
+    }
+}
+
+impl<T: Checked> Arithmetic<T> for Checking {
+    fn add(a: T, b: T) -> Option<T> {
+        a.add_checked(b)
+    }
+
+    fn mul(a: T, b: T) -> Option<T> {
+        a.mul_checked(b)
+    }
+}
+
+// Why a step couldn't be done.
+enum Failure {
+    InexactTurn,
+    Overflow,
+}
+
+fn neg<T: Scalar, A: Arithmetic<T>>(a: T) -> Option<T> {
+    A::mul(a, T::from_i32(-1))
+}
+
+fn add<T: Scalar, A: Arithmetic<T>>(u: Vector3<T>, v: Vector3<T>) -> Option<Vector3<T>> {
+    Some([
+        A::add(u[0], v[0])?,
+        A::add(u[1], v[1])?,
+        A::add(u[2], v[2])?,
+    ])
+}
+
+fn scale<T: Scalar, A: Arithmetic<T>>(k: T, v: Vector3<T>) -> Option<Vector3<T>> {
+    Some([A::mul(k, v[0])?, A::mul(k, v[1])?, A::mul(k, v[2])?])
+}
+
+fn dot<T: Scalar, A: Arithmetic<T>>(u: Vector3<T>, v: Vector3<T>) -> Option<T> {
+    A::add(
+        A::add(A::mul(u[0], v[0])?, A::mul(u[1], v[1])?)?,
+        A::mul(u[2], v[2])?,
+    )
+}
+
+fn cross<T: Scalar, A: Arithmetic<T>>(u: Vector3<T>, v: Vector3<T>) -> Option<Vector3<T>> {
+    let term = |a: T, b: T, c: T, d: T| A::add(A::mul(a, b)?, neg::<T, A>(A::mul(c, d)?)?);
+    Some([
+        term(u[1], v[2], u[2], v[1])?,
+        term(u[2], v[0], u[0], v[2])?,
+        term(u[0], v[1], u[1], v[0])?,
+    ])
+}
+
+// Turns `v` anticlockwise by `degrees` about the unit vector `axis` (as seen with the axis
+// pointing at you).
+fn rotate<T: Scalar, A: Arithmetic<T>>(
+    v: Vector3<T>,
+    axis: Vector3<T>,
+    degrees: i32,
+) -> Result<Vector3<T>, Failure> {
+    let (cos, sin) = T::cos_sin(degrees).ok_or(Failure::InexactTurn)?;
+    let rotated = || {
+        let along = A::mul(
+            dot::<T, A>(axis, v)?,
+            A::add(T::from_i32(1), neg::<T, A>(cos)?)?,
+        )?;
+        let turned = add::<T, A>(
+            scale::<T, A>(cos, v)?,
+            scale::<T, A>(sin, cross::<T, A>(axis, v)?)?,
+        )?;
+        add::<T, A>(turned, scale::<T, A>(along, axis)?)
+    };
+    rotated().ok_or(Failure::Overflow)
+}
+
+fn translation<T: Scalar, A: Arithmetic<T>>(mov: &Move) -> Option<Vector3<T>> {
+    let zero = T::from_i32(0);
+    let back = |amt: i32| neg::<T, A>(T::from_i32(amt));
+    Some(match *mov {
+        Move::N(amt) => [zero, T::from_i32(amt), zero],
+        Move::S(amt) => [zero, back(amt)?, zero],
+        Move::E(amt) => [T::from_i32(amt), zero, zero],
+        Move::W(amt) => [back(amt)?, zero, zero],
+        Move::U(amt) => [zero, zero, T::from_i32(amt)],
+        Move::D(amt) => [zero, zero, back(amt)?],
+        _ => unreachable!("not a translation"),
+    })
+}
+
+// In ship mode turns are the ship's own: L and R yaw about its deck's up, P pitches its nose up
+// towards the deck's up, and B rolls it clockwise looking forward. In waypoint mode the waypoint
+// turns about fixed axes the same way it would around a ship facing east on an even keel: L and R
+// about up (as in 2D), P from east towards up, and B from up towards south.
+fn step_with<T: Scalar, A: Arithmetic<T>>(
+    state: &State3<T>,
+    mov: &Move,
+    mode: Mode,
+) -> Result<State3<T>, Failure> {
+    let mut next = *state;
+    let east = [T::from_i32(1), T::from_i32(0), T::from_i32(0)];
+    let north = [T::from_i32(0), T::from_i32(1), T::from_i32(0)];
+    let rotate = rotate::<T, A>;
+    let overflow = |v: Option<Vector3<T>>| v.ok_or(Failure::Overflow);
+    let moved =
+        |by: Vector3<T>| overflow(translation::<T, A>(mov).and_then(|t| add::<T, A>(by, t)));
+    match (mode, *mov) {
+        (_, Move::F(amt)) => {
+            let ahead = overflow(scale::<T, A>(T::from_i32(amt), state.vector))?;
+            next.position = overflow(add::<T, A>(state.position, ahead))?;
+        }
+        (Mode::Ship, Move::L(amt)) => next.vector = rotate(state.vector, state.up, amt)?,
+        (Mode::Ship, Move::R(amt)) => next.vector = rotate(state.vector, state.up, -amt)?,
+        (Mode::Ship, Move::P(amt)) => {
+            let left = overflow(cross::<T, A>(state.up, state.vector))?;
+            next.vector = rotate(state.vector, left, -amt)?;
+            next.up = rotate(state.up, left, -amt)?;
+        }
+        (Mode::Ship, Move::B(amt)) => next.up = rotate(state.up, state.vector, amt)?,
+        (Mode::Ship, _) => next.position = moved(state.position)?,
+        (Mode::Waypoint, Move::L(amt)) => next.vector = rotate(state.vector, state.up, amt)?,
+        (Mode::Waypoint, Move::R(amt)) => next.vector = rotate(state.vector, state.up, -amt)?,
+        (Mode::Waypoint, Move::P(amt)) => next.vector = rotate(state.vector, north, -amt)?,
+        (Mode::Waypoint, Move::B(amt)) => next.vector = rotate(state.vector, east, amt)?,
+        (Mode::Waypoint, _) => next.vector = moved(state.vector)?,
+    }
+    Ok(next)
+}
+
+// None if `T` can't do the turn exactly. Overflow isn't checked: see `simulate_checked`.
+pub fn step<T: Scalar>(state: &State3<T>, mov: &Move, mode: Mode) -> Option<State3<T>> {
+    step_with::<T, Plain>(state, mov, mode).ok()
+}
+
+// None if some turn can't be done exactly in `T`.
+pub fn simulate<T: Scalar>(moves: &[Move], mode: Mode, start: State3<T>) -> Option<State3<T>> {
+    moves
+        .iter()
+        .try_fold(start, |state, mov| step(&state, mov, mode))
+}
+
+// Like `day12::simulate_checked`: stops at the first move that can't be done exactly or that
+// overflows `T`, and says which one it was (counting from 1).
+pub fn simulate_checked<T: Checked>(
+    moves: &[Move],
+    mode: Mode,
+    start: State3<T>,
+) -> Result<State3<T>, String> {
+    moves.iter().enumerate().try_fold(start, |state, (i, mov)| {
+        step_with::<T, Checking>(&state, mov, mode).map_err(|failure| match failure {
+            Failure::InexactTurn => {
+                format!("move {}: {:?} {}", i + 1, mov, Unsupported::InexactTurn)
+            }
+            Failure::Overflow => format!(
+                "move {}: {:?} overflows {}",
+                i + 1,
+                mov,
+                std::any::type_name::<T>()
+            ),
+        })
+    })
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Navigation3 {
+    Exact(State3<i64>),
+    Approximate(State3<f64>),
+}
+
+impl Navigation3 {
+    pub fn manhattan(&self) -> f64 {
+        match self {
+            Navigation3::Exact(state) => state
+                .position
+                .iter()
+                .map(|c| c.unsigned_abs() as u128)
+                .sum::<u128>() as f64,
+            Navigation3::Approximate(state) => state.position.iter().map(|c| c.abs()).sum(),
+        }
+    }
+}
+
+// Like `day12::navigate`: integers while every turn is a multiple of 90 degrees and nothing
+// overflows an i64, floating point otherwise.
+pub fn navigate(moves: &[Move], mode: Mode) -> Navigation3 {
+    match simulate_checked(moves, mode, start(mode)) {
+        Ok(state) => Navigation3::Exact(state),
+        Err(_) => Navigation3::Approximate(
+            simulate(moves, mode, start(mode)).expect("floats can turn any angle"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12::{self, parse_input, parse_moves, Dimensions, Navigation, Turns};
+    use Move::*;
+
+    fn parse_3d(input: &str) -> Vec<Move> {
+        parse_moves(input, Dimensions::Space, Turns::Any).unwrap()
+    }
+
+    #[test]
+    fn plane_routes_are_unchanged() {
+        let real =
+            std::fs::read_to_string("src/day12/input.in").expect("failed to read day12 input");
+        for input in ["F10\nN3\nF7\nR90\nF11", real.as_str()].iter() {
+            let moves = parse_input(input);
+            for mode in [Mode::Ship, Mode::Waypoint].iter() {
+                let flat = match day12::navigate(&moves, *mode) {
+                    Navigation::Exact(state) => state,
+                    other => panic!("unexpected {:?}", other),
+                };
+                match navigate(&moves, *mode) {
+                    Navigation3::Exact(state) => {
+                        assert_eq!([flat.position[0], flat.position[1], 0], state.position);
+                        assert_eq!([flat.vector[0], flat.vector[1], 0], state.vector);
+                        assert_eq!([0, 0, 1], state.up);
+                    }
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+        }
+        let moves = parse_input(&real);
+        assert_eq!(1032.0, navigate(&moves, Mode::Ship).manhattan());
+        assert_eq!(156735.0, navigate(&moves, Mode::Waypoint).manhattan());
+    }
+
+    #[test]
+    fn fly_the_ship() {
+        assert_eq!(
+            vec![U(3), P(90), B(180), D(2), F(1)],
+            parse_3d("U3\nP90\nB180\nD2\nF1")
+        );
+        // straight up, yaw left (which is now north, with the deck facing west), then roll so the
+        // deck faces up again
+        let moves = parse_3d("P90\nF5\nL90\nF2\nB90\nU1");
+        assert_eq!(
+            Navigation3::Exact(State3 {
+                position: [0, 2, 6],
+                vector: [0, 1, 0],
+                up: [0, 0, 1]
+            }),
+            navigate(&moves, Mode::Ship)
+        );
+        assert_eq!(
+            [0, 0, -1],
+            simulate(&[P(270), F(1)], Mode::Ship, start::<i64>(Mode::Ship))
+                .unwrap()
+                .position
+        );
+        assert_eq!(
+            Err("move 1: P(45) isn't a quarter turn".to_string()),
+            simulate_checked(&[P(45), F(2)], Mode::Ship, start::<i64>(Mode::Ship))
+        );
+        match navigate(&[P(45), F(2)], Mode::Ship) {
+            Navigation3::Approximate(state) => {
+                let half = 0.5f64.sqrt();
+                assert!((state.position[0] - 2.0 * half).abs() < 1e-9);
+                assert!((state.position[2] - 2.0 * half).abs() < 1e-9);
+            }
+            exact => panic!("expected an approximate result, got {:?}", exact),
+        }
+    }
+
+    #[test]
+    fn fly_the_waypoint() {
+        let moves = parse_3d("U5\nF2\nP90\nF1\nB90\nF1");
+        assert_eq!(
+            Navigation3::Exact(State3 {
+                position: [10, -7, 21],
+                vector: [-5, -10, 1],
+                up: [0, 0, 1]
+            }),
+            navigate(&moves, Mode::Waypoint)
+        );
+        // turning the waypoint the same way as the ship, from the start, points it the same way
+        for turn in [L(90), R(90), P(90), B(90), P(270)].iter() {
+            let ship = simulate(&[*turn], Mode::Ship, start::<i64>(Mode::Ship)).unwrap();
+            let mut state = start::<i64>(Mode::Waypoint);
+            state.vector = [1, 0, 0];
+            let waypoint = simulate(&[*turn], Mode::Waypoint, state).unwrap();
+            assert_eq!(ship.vector, waypoint.vector);
+        }
+    }
+
+    #[test]
+    fn overflow_goes_approximate() {
+        let moves = [
+            U(2_000_000_000),
+            F(2_000_000_000),
+            F(2_000_000_000),
+            F(2_000_000_000),
+        ];
+        assert_eq!(
+            Err("move 4: F(2000000000) overflows i64".to_string()),
+            simulate_checked(&moves, Mode::Waypoint, start::<i64>(Mode::Waypoint))
+        );
+        let fits = simulate_checked(&moves[..3], Mode::Waypoint, start::<i64>(Mode::Waypoint));
+        assert_eq!(
+            Ok([40_000_000_000, 4_000_000_000, 8_000_000_000_000_000_000]),
+            fits.map(|s| s.position)
+        );
+        match navigate(&moves, Mode::Waypoint) {
+            Navigation3::Approximate(state) => assert_eq!(1.2e19, state.position[2]),
+            exact => panic!("expected an approximate result, got {:?}", exact),
+        }
+        // the distance itself is past i64 without overflowing
+        let far = Navigation3::Exact(State3 {
+            position: [i64::MAX, i64::MIN, 1],
+            vector: [0; 3],
+            up: [0; 3],
+        });
+        assert_eq!(2f64.powi(64), far.manhattan());
+    }
+}
@@ -0,0 +1,240 @@
+use super::{Mode, Move, Scalar, State, Transform, Unsupported};
+
+// Every state a route goes through, the starting one first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trajectory<T> {
+    pub mode: Mode,
+    pub states: Vec<State<T>>,
+}
+
+pub struct SvgStyle {
+    pub ship_stroke: String,
+    pub waypoint_stroke: String,
+    pub stroke_width: f64,
+    // room left around the paths, in grid units
+    pub margin: f64,
+}
+
+impl Default for SvgStyle {
+    fn default() -> SvgStyle {
+        SvgStyle {
+            ship_stroke: "black".to_string(),
+            waypoint_stroke: "orange".to_string(),
+            stroke_width: 1.0,
+            margin: 10.0,
+        }
+    }
+}
+
+fn points(path: &[[f64; 2]]) -> String {
+    path.iter()
+        .map(|[x, y]| format!("{},{}", x, 0.0 - y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl<T: Scalar> Trajectory<T> {
+    // Err if `Transform::of_move` can't do some move in `T`. Overflow isn't checked (it panics in
+    // debug builds and wraps in release); `simulate_checked` will say whether a route fits.
+    pub fn record(
+        moves: &[Move],
+        mode: Mode,
+        start: State<T>,
+    ) -> Result<Trajectory<T>, Unsupported> {
+        let mut states = vec![start];
+        for mov in moves {
+            let next = Transform::of_move(mov, mode)?.apply(&states[states.len() - 1]);
+            states.push(next);
+        }
+        Ok(Trajectory { mode, states })
+    }
+
+    pub fn ship_path(&self) -> Vec<[f64; 2]> {
+        self.states
+            .iter()
+            .map(|state| [state.position[0].to_f64(), state.position[1].to_f64()])
+            .collect()
+    }
+
+    // Where the waypoint actually is (not relative to the ship); empty in ship mode.
+    pub fn waypoint_path(&self) -> Vec<[f64; 2]> {
+        match self.mode {
+            Mode::Ship => vec![],
+            Mode::Waypoint => self
+                .states
+                .iter()
+                .map(|state| {
+                    [
+                        (state.position[0] + state.vector[0]).to_f64(),
+                        (state.position[1] + state.vector[1]).to_f64(),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    // South-west and north-east corners of the smallest box around the ship's path.
+    pub fn bounding_box(&self) -> ([f64; 2], [f64; 2]) {
+        bounds(&self.ship_path())
+    }
+
+    // Length of the ship's path, diagonal legs included.
+    pub fn distance_travelled(&self) -> f64 {
+        self.ship_path()
+            .windows(2)
+            .map(|leg| (leg[1][0] - leg[0][0]).hypot(leg[1][1] - leg[0][1]))
+            .sum()
+    }
+
+    // Largest Manhattan distance of the ship from the origin along the way.
+    pub fn furthest_from_origin(&self) -> f64 {
+        self.ship_path()
+            .iter()
+            .map(|[x, y]| x.abs() + y.abs())
+            .fold(0.0, f64::max)
+    }
+
+    // North is up. The ship's path is the polyline with class "ship" and the waypoint's (in
+    // waypoint mode) the one with class "waypoint".
+    pub fn to_svg(&self, style: &SvgStyle) -> String {
+        let (ship, waypoint) = (self.ship_path(), self.waypoint_path());
+        let everything = ship
+            .iter()
+            .chain(waypoint.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        let ([min_x, min_y], [max_x, max_y]) = bounds(&everything);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_x - style.margin,
+            0.0 - max_y - style.margin,
+            max_x - min_x + 2.0 * style.margin,
+            max_y - min_y + 2.0 * style.margin
+        );
+        let paths = [
+            ("ship", &ship, &style.ship_stroke),
+            ("waypoint", &waypoint, &style.waypoint_stroke),
+        ];
+        for (class, path, stroke) in paths.iter().filter(|(_, path, _)| !path.is_empty()) {
+            svg.push_str(&format!(
+                "  <polyline class=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" \
+                 points=\"{}\"/>\n",
+                class,
+                stroke,
+                style.stroke_width,
+                points(path)
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn bounds(path: &[[f64; 2]]) -> ([f64; 2], [f64; 2]) {
+    if path.is_empty() {
+        return ([0.0; 2], [0.0; 2]);
+    }
+    path.iter().fold(
+        ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]),
+        |([min_x, min_y], [max_x, max_y]), [x, y]| {
+            (
+                [min_x.min(*x), min_y.min(*y)],
+                [max_x.max(*x), max_y.max(*y)],
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day12::{navigate, parse_input, Navigation};
+
+    static SAMPLE_INPUT: &str = "F10\nN3\nF7\nR90\nF11";
+
+    #[test]
+    fn record_sample_routes() {
+        let moves = parse_input(SAMPLE_INPUT);
+        let ship = Trajectory::<i64>::record(&moves, Mode::Ship, Mode::Ship.start()).unwrap();
+        assert_eq!(
+            vec![
+                [0.0, 0.0],
+                [10.0, 0.0],
+                [10.0, 3.0],
+                [17.0, 3.0],
+                [17.0, 3.0],
+                [17.0, -8.0]
+            ],
+            ship.ship_path()
+        );
+        assert!(ship.waypoint_path().is_empty());
+        assert_eq!(([0.0, -8.0], [17.0, 3.0]), ship.bounding_box());
+        assert_eq!(31.0, ship.distance_travelled());
+        assert_eq!(25.0, ship.furthest_from_origin());
+        match navigate(&moves, Mode::Ship) {
+            Navigation::Exact(end) => assert_eq!(Some(&end), ship.states.last()),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let waypoint =
+            Trajectory::<i64>::record(&moves, Mode::Waypoint, Mode::Waypoint.start()).unwrap();
+        assert_eq!(
+            vec![
+                [0.0, 0.0],
+                [100.0, 10.0],
+                [100.0, 10.0],
+                [170.0, 38.0],
+                [170.0, 38.0],
+                [214.0, -72.0]
+            ],
+            waypoint.ship_path()
+        );
+        assert_eq!(
+            vec![
+                [10.0, 1.0],
+                [110.0, 11.0],
+                [110.0, 14.0],
+                [180.0, 42.0],
+                [174.0, 28.0],
+                [218.0, -82.0]
+            ],
+            waypoint.waypoint_path()
+        );
+        assert_eq!(286.0, waypoint.furthest_from_origin());
+
+        assert!(Trajectory::<i64>::record(&[Move::L(45)], Mode::Ship, Mode::Ship.start()).is_err());
+        let diagonal = Trajectory::<f64>::record(
+            &[Move::L(45), Move::F(10), Move::F(10)],
+            Mode::Ship,
+            Mode::Ship.start(),
+        )
+        .unwrap();
+        assert!((diagonal.distance_travelled() - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn svg_export() {
+        let moves = parse_input("F2\nN1\nL90\nF3");
+        let style = SvgStyle {
+            ship_stroke: "navy".to_string(),
+            waypoint_stroke: "red".to_string(),
+            stroke_width: 0.5,
+            margin: 1.0,
+        };
+        let ship = Trajectory::<i64>::record(&moves, Mode::Ship, Mode::Ship.start()).unwrap();
+        assert_eq!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -5 4 6\">
+  <polyline class=\"ship\" fill=\"none\" stroke=\"navy\" stroke-width=\"0.5\" \
+             points=\"0,0 2,0 2,-1 2,-1 2,-4\"/>
+</svg>
+",
+            ship.to_svg(&style)
+        );
+        let waypoint =
+            Trajectory::<i64>::record(&moves, Mode::Waypoint, Mode::Waypoint.start()).unwrap();
+        let svg = waypoint.to_svg(&style);
+        assert!(svg.contains("class=\"ship\" fill=\"none\" stroke=\"navy\""));
+        assert!(svg.contains("class=\"waypoint\" fill=\"none\" stroke=\"red\""));
+        assert!(svg.contains("points=\"10,-1 30,-3 30,-4 18,-12 12,-42\""));
+    }
+}
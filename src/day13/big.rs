@@ -0,0 +1,244 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Natural number of any size: base 2^32 digits, least significant first, with no zero digits on
+// the end (so zero has no digits at all).
+#[derive(Clone, PartialEq, Eq)]
+pub struct BigNat {
+    digits: Vec<u32>,
+}
+
+impl BigNat {
+    pub fn zero() -> BigNat {
+        BigNat { digits: vec![] }
+    }
+
+    pub fn from_u128(mut n: u128) -> BigNat {
+        let mut digits = vec![];
+        while n > 0 {
+            digits.push(n as u32);
+            n >>= 32;
+        }
+        BigNat { digits }
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.digits.len() > 4 {
+            return None;
+        }
+        Some(
+            self.digits
+                .iter()
+                .rev()
+                .fold(0, |n, digit| n << 32 | *digit as u128),
+        )
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn trimmed(mut digits: Vec<u32>) -> BigNat {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigNat { digits }
+    }
+
+    fn bits(&self) -> usize {
+        match self.digits.last() {
+            None => 0,
+            Some(top) => 32 * self.digits.len() - top.leading_zeros() as usize,
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.digits[i / 32] >> (i % 32) & 1 == 1
+    }
+
+    pub fn add(&self, other: &BigNat) -> BigNat {
+        let mut digits = vec![];
+        let mut carry = 0u64;
+        for i in 0..self.digits.len().max(other.digits.len()) {
+            let sum = carry
+                + *self.digits.get(i).unwrap_or(&0) as u64
+                + *other.digits.get(i).unwrap_or(&0) as u64;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        BigNat::trimmed(digits)
+    }
+
+    // None if `other` is the bigger one.
+    pub fn sub(&self, other: &BigNat) -> Option<BigNat> {
+        if *self < *other {
+            return None;
+        }
+        let mut digits = vec![];
+        let mut borrow = 0i64;
+        for (i, digit) in self.digits.iter().enumerate() {
+            let mut difference = *digit as i64 - *other.digits.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            digits.push(difference as u32);
+        }
+        Some(BigNat::trimmed(digits))
+    }
+
+    pub fn mul(&self, other: &BigNat) -> BigNat {
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.digits.iter().enumerate() {
+                let product = *a as u64 * *b as u64 + digits[i + j] as u64 + carry;
+                digits[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        BigNat::trimmed(digits)
+    }
+
+    // Quotient and remainder, one bit at a time. Panics on division by zero.
+    pub fn div_rem(&self, other: &BigNat) -> (BigNat, BigNat) {
+        assert!(!other.is_zero(), "division by zero");
+        let mut quotient = vec![0u32; self.digits.len()];
+        let mut remainder = BigNat::zero();
+        for i in (0..self.bits()).rev() {
+            remainder = remainder.add(&remainder);
+            if self.bit(i) {
+                remainder = remainder.add(&BigNat::from_u128(1));
+            }
+            if remainder >= *other {
+                remainder = remainder.sub(other).unwrap();
+                quotient[i / 32] |= 1 << (i % 32);
+            }
+        }
+        (BigNat::trimmed(quotient), remainder)
+    }
+
+    fn div_small(&self, divisor: u32) -> (BigNat, u32) {
+        let mut quotient = vec![0u32; self.digits.len()];
+        let mut remainder = 0u64;
+        for (i, digit) in self.digits.iter().enumerate().rev() {
+            let current = remainder << 32 | *digit as u64;
+            quotient[i] = (current / divisor as u64) as u32;
+            remainder = current % divisor as u64;
+        }
+        (BigNat::trimmed(quotient), remainder as u32)
+    }
+}
+
+impl Ord for BigNat {
+    fn cmp(&self, other: &BigNat) -> Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigNat {
+    fn partial_cmp(&self, other: &BigNat) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigNat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // nine decimal digits at a time, least significant first
+        let mut chunks = vec![];
+        let mut rest = self.clone();
+        loop {
+            let (quotient, chunk) = rest.div_small(1_000_000_000);
+            chunks.push(chunk);
+            if quotient.is_zero() {
+                break;
+            }
+            rest = quotient;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BigNat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated(count: usize, seed: u64) -> Vec<u128> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                let mut next = || {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    state
+                };
+                // a spread of sizes
+                let n = (next() as u128) << 64 | next() as u128;
+                n >> (next() % 128)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_u128() {
+        let numbers = generated(60, 5);
+        for a in numbers.iter() {
+            let big_a = BigNat::from_u128(*a);
+            assert_eq!(Some(*a), big_a.to_u128());
+            assert_eq!(a.to_string(), big_a.to_string());
+            for b in numbers.iter() {
+                let big_b = BigNat::from_u128(*b);
+                assert_eq!(a.cmp(b), big_a.cmp(&big_b));
+                assert_eq!(a.checked_add(*b), big_a.add(&big_b).to_u128());
+                assert_eq!(
+                    a.checked_sub(*b),
+                    big_a.sub(&big_b).map(|d| d.to_u128().unwrap())
+                );
+                assert_eq!(a.checked_mul(*b), big_a.mul(&big_b).to_u128());
+                if *b != 0 {
+                    let (q, r) = big_a.div_rem(&big_b);
+                    assert_eq!((Some(a / b), Some(a % b)), (q.to_u128(), r.to_u128()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn past_u128() {
+        let max = BigNat::from_u128(u128::MAX);
+        let one = BigNat::from_u128(1);
+        let two_to_128 = max.add(&one);
+        assert_eq!(None, two_to_128.to_u128());
+        assert_eq!(
+            "340282366920938463463374607431768211456",
+            two_to_128.to_string()
+        );
+        assert_eq!(
+            "115792089237316195423570985008687907852589419931798687112530834793049593217025",
+            max.mul(&max).to_string()
+        );
+        let (q, r) = max.mul(&max).add(&two_to_128).div_rem(&max);
+        assert_eq!((max.add(&one), one.clone()), (q, r));
+        assert_eq!(Some(max.clone()), two_to_128.sub(&one));
+        assert_eq!(None, one.sub(&two_to_128));
+        assert_eq!("0", BigNat::zero().to_string());
+        assert_eq!("1000000000", BigNat::from_u128(1_000_000_000).to_string());
+    }
+}
@@ -1,3 +1,9 @@
+pub mod big;
+pub mod number_theory;
+
+use big::BigNat;
+use number_theory::{crt, try_crt, Congruence, Natural};
+
 fn parse(input: &str) -> (i32, Vec<(usize, i32)>) {
     if let [time, buses_text] = input.lines().collect::<Vec<_>>()[0..2] {
         (
@@ -36,24 +42,49 @@ pub fn solve_p1(input: &str) -> i32 {
     (best - time) * best_bus
 }
 
-pub fn solve_p2(input: &str) -> i64 {
+// Worked out in u128 if that's big enough, and with arbitrary precision if it isn't.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    Fits(Congruence<u128>),
+    Huge(Congruence<BigNat>),
+}
+
+fn congruences<T: Natural>(buses: &[(usize, i32)]) -> Vec<Congruence<T>> {
+    buses
+        .iter()
+        .map(|(idx, v)| {
+            let v = *v as u32;
+            Congruence {
+                residue: T::from_u32((v - (*idx as u64 % v as u64) as u32) % v),
+                modulus: T::from_u32(v),
+            }
+        })
+        .collect()
+}
+
+// Every time at which each bus leaves as many minutes after it as its place in the list, as one
+// congruence; bus IDs don't have to be coprime.
+pub fn schedule(input: &str) -> Result<Schedule, String> {
     let (_, buses) = parse(input);
-    let congruences = buses.iter().map(|(idx, v)| {
-        let mut rem = v - *idx as i32;
-        while rem < 0 {
-            rem += v;
-        }
-        (rem % v, v)
-    });
-    let mut start: i64 = 0;
-    let mut acc: i64 = 1;
-    for (rem, v) in congruences {
-        while start % *v as i64 != rem as i64 {
-            start += acc
-        }
-        acc *= *v as i64
+    if let Some((_, bus)) = buses.iter().find(|(_, v)| *v <= 0) {
+        return Err(format!("bus {} isn't a positive ID", bus));
+    }
+    match try_crt(&congruences::<u128>(&buses)) {
+        Some(fits) => fits.map(Schedule::Fits),
+        None => crt(&congruences::<BigNat>(&buses)).map(Schedule::Huge),
+    }
+}
+
+pub fn solve_p2(input: &str) -> u128 {
+    match schedule(input) {
+        Ok(Schedule::Fits(earliest)) => earliest.residue,
+        // the buses can come round together too rarely for a u128 and still do it early on
+        Ok(Schedule::Huge(earliest)) => earliest
+            .residue
+            .to_u128()
+            .unwrap_or_else(|| panic!("{} doesn't fit in a u128", earliest.residue)),
+        Err(err) => panic!("{}", err),
     }
-    start
 }
 
 #[cfg(test)]
@@ -92,4 +123,55 @@ mod tests {
         assert_eq!(779210, solve_p2(SAMPLE_INPUT2));
         assert_eq!(1202161486, solve_p2(SAMPLE_INPUT4));
     }
+
+    #[test]
+    fn non_coprime_buses() {
+        // bus 4 at t, bus 6 at t + 2
+        assert_eq!(
+            Ok(Schedule::Fits(Congruence {
+                residue: 4,
+                modulus: 12
+            })),
+            schedule("939\n4,x,6")
+        );
+        // t even and t + 1 even
+        assert!(schedule("939\n4,6").unwrap_err().starts_with("no solution"));
+    }
+
+    #[test]
+    fn large_primes() {
+        // the product of these just fits in a u128...
+        let four = "0\n2147483647,x,2147483629,2147483587,2147483579";
+        assert_eq!(5633961218970249525755058253563416340, solve_p2(four));
+        // ...and with one more it doesn't
+        match schedule(&format!("{},2147483563", four)) {
+            Ok(Schedule::Huge(earliest)) => {
+                assert_eq!(
+                    "2264292077495566696154052888553231315425303131",
+                    earliest.residue.to_string()
+                );
+                assert_eq!(
+                    "45671921168693645933699105804560590380377589537",
+                    earliest.modulus.to_string()
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // every prime up to 113 at its own number less one: the LCM is far past a u128, but
+        // t = 1 works for all of them
+        let mut buses = vec!["x".to_string(); 113];
+        let primes = [
+            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+            89, 97, 101, 103, 107, 109, 113,
+        ];
+        for p in primes.iter() {
+            buses[p - 1] = p.to_string();
+        }
+        let input = format!("0\n{}", buses.join(","));
+        match schedule(&input) {
+            Ok(Schedule::Huge(earliest)) => assert_eq!(Some(1), earliest.residue.to_u128()),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(1, solve_p2(&input));
+    }
 }
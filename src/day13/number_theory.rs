@@ -0,0 +1,362 @@
+use super::big::BigNat;
+use std::fmt::Debug;
+
+// (g, x, y) with a x + b y = g = gcd(a, b) and g >= 0.
+pub fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+// Numbers the CRT can run on. Only non-negative values are used, and arithmetic that doesn't
+// fit says so instead of wrapping.
+pub trait Natural: Clone + Debug + PartialOrd {
+    fn from_u32(n: u32) -> Self;
+    fn plus(&self, other: &Self) -> Option<Self>;
+    // None below zero too
+    fn minus(&self, other: &Self) -> Option<Self>;
+    fn times(&self, other: &Self) -> Option<Self>;
+    fn div_rem(&self, other: &Self) -> (Self, Self);
+}
+
+impl Natural for i64 {
+    fn from_u32(n: u32) -> i64 {
+        n as i64
+    }
+
+    fn plus(&self, other: &i64) -> Option<i64> {
+        self.checked_add(*other)
+    }
+
+    fn minus(&self, other: &i64) -> Option<i64> {
+        self.checked_sub(*other).filter(|d| *d >= 0)
+    }
+
+    fn times(&self, other: &i64) -> Option<i64> {
+        self.checked_mul(*other)
+    }
+
+    fn div_rem(&self, other: &i64) -> (i64, i64) {
+        (self / other, self % other)
+    }
+}
+
+impl Natural for u64 {
+    fn from_u32(n: u32) -> u64 {
+        n as u64
+    }
+
+    fn plus(&self, other: &u64) -> Option<u64> {
+        self.checked_add(*other)
+    }
+
+    fn minus(&self, other: &u64) -> Option<u64> {
+        self.checked_sub(*other)
+    }
+
+    fn times(&self, other: &u64) -> Option<u64> {
+        self.checked_mul(*other)
+    }
+
+    fn div_rem(&self, other: &u64) -> (u64, u64) {
+        (self / other, self % other)
+    }
+}
+
+impl Natural for u128 {
+    fn from_u32(n: u32) -> u128 {
+        n as u128
+    }
+
+    fn plus(&self, other: &u128) -> Option<u128> {
+        self.checked_add(*other)
+    }
+
+    fn minus(&self, other: &u128) -> Option<u128> {
+        self.checked_sub(*other)
+    }
+
+    fn times(&self, other: &u128) -> Option<u128> {
+        self.checked_mul(*other)
+    }
+
+    fn div_rem(&self, other: &u128) -> (u128, u128) {
+        (self / other, self % other)
+    }
+}
+
+impl Natural for BigNat {
+    fn from_u32(n: u32) -> BigNat {
+        BigNat::from_u128(n as u128)
+    }
+
+    fn plus(&self, other: &BigNat) -> Option<BigNat> {
+        Some(self.add(other))
+    }
+
+    fn minus(&self, other: &BigNat) -> Option<BigNat> {
+        self.sub(other)
+    }
+
+    fn times(&self, other: &BigNat) -> Option<BigNat> {
+        Some(self.mul(other))
+    }
+
+    fn div_rem(&self, other: &BigNat) -> (BigNat, BigNat) {
+        BigNat::div_rem(self, other)
+    }
+}
+
+pub fn gcd<T: Natural>(a: &T, b: &T) -> T {
+    let zero = T::from_u32(0);
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while b != zero {
+        let rem = a.div_rem(&b).1;
+        a = b;
+        b = rem;
+    }
+    a
+}
+
+// x in [0, m) with a x = 1 (mod m), if a and m are coprime. The Bezout coefficient is kept
+// reduced mod m so it never goes negative. None if something overflows too.
+pub fn mod_inverse<T: Natural>(a: &T, m: &T) -> Option<T> {
+    let zero = T::from_u32(0);
+    let (mut old_r, mut r) = (a.div_rem(m).1, m.clone());
+    // old_r = old_s a and r = s a, mod m
+    let (mut old_s, mut s) = (T::from_u32(1), zero.clone());
+    while r != zero {
+        let (q, rem) = old_r.div_rem(&r);
+        let qs = q.times(&s)?.div_rem(m).1;
+        let next_s = old_s.plus(m)?.minus(&qs)?.div_rem(m).1;
+        old_r = r;
+        r = rem;
+        old_s = s;
+        s = next_s;
+    }
+    (old_r == T::from_u32(1)).then(|| old_s.div_rem(m).1)
+}
+
+// x = residue (mod modulus), with residue in [0, modulus).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Congruence<T> {
+    pub residue: T,
+    pub modulus: T,
+}
+
+impl Congruence<i64> {
+    pub fn new(residue: i64, modulus: i64) -> Congruence<i64> {
+        Congruence {
+            residue: residue.rem_euclid(modulus),
+            modulus,
+        }
+    }
+}
+
+impl<T: Natural> Congruence<T> {
+    // The congruence that holds exactly when both of these do: Err if there isn't one, None if
+    // it doesn't fit in `T`. The moduli don't have to be coprime; the new one is their LCM.
+    fn try_merge(&self, other: &Congruence<T>) -> Option<Result<Congruence<T>, String>> {
+        let (m1, m2) = (&self.modulus, &other.modulus);
+        let a1 = self.residue.div_rem(m1).1;
+        let a2 = other.residue.div_rem(m2).1;
+        // (a2 - a1) mod m2, which has to be a multiple of the common factor
+        let a1_mod_m2 = a1.div_rem(m2).1;
+        let difference = a2.plus(m2)?.minus(&a1_mod_m2)?.div_rem(m2).1;
+        let g = gcd(m1, m2);
+        let (quotient, rem) = difference.div_rem(&g);
+        if rem != T::from_u32(0) {
+            return Some(Err(format!(
+                "no solution: {:?} contradicts {:?}",
+                other, self
+            )));
+        }
+        // a1 + m1 k, with m1 k = a2 - a1 (mod m2), is below the LCM m1 (m2 / g)
+        let step = m2.div_rem(&g).0;
+        // m1 / g and m2 / g are coprime, so only overflow stops this
+        let inverse = mod_inverse(&m1.div_rem(&g).0, &step)?;
+        let k = quotient.times(&inverse)?.div_rem(&step).1;
+        Some(Ok(Congruence {
+            residue: a1.plus(&m1.times(&k)?)?,
+            modulus: m1.times(&step)?,
+        }))
+    }
+
+    pub fn merge(&self, other: &Congruence<T>) -> Result<Congruence<T>, String> {
+        self.try_merge(other)
+            .unwrap_or_else(|| Err(overflow::<T>()))
+    }
+}
+
+fn overflow<T>() -> String {
+    format!(
+        "the combined modulus overflows {}",
+        std::any::type_name::<T>()
+    )
+}
+
+// Chinese remainder theorem for any positive moduli: every x satisfying all of `congruences` is
+// the one congruence this returns (Ok inside), or there's no such x at all (Err inside). None if
+// the working doesn't fit in `T`.
+pub fn try_crt<T: Natural>(congruences: &[Congruence<T>]) -> Option<Result<Congruence<T>, String>> {
+    let zero = T::from_u32(0);
+    if let Some(bad) = congruences.iter().find(|c| c.modulus <= zero) {
+        return Some(Err(format!("{:?} doesn't have a positive modulus", bad)));
+    }
+    let mut merged = Congruence {
+        residue: zero,
+        modulus: T::from_u32(1),
+    };
+    for next in congruences {
+        merged = match merged.try_merge(next)? {
+            Ok(merged) => merged,
+            err => return Some(err),
+        };
+    }
+    Some(Ok(merged))
+}
+
+pub fn crt<T: Natural>(congruences: &[Congruence<T>]) -> Result<Congruence<T>, String> {
+    try_crt(congruences).unwrap_or_else(|| Err(overflow::<T>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcds_and_inverses() {
+        for a in -30..30 {
+            for b in -30..30 {
+                let (g, x, y) = extended_gcd(a, b);
+                assert_eq!(g, a * x + b * y);
+                assert!(g >= 0);
+                if g != 0 {
+                    assert_eq!(0, a % g);
+                    assert_eq!(0, b % g);
+                }
+            }
+        }
+        assert_eq!((6, -1, 1), extended_gcd(12, 18));
+        assert_eq!(Some(4i64), mod_inverse(&3, &11));
+        assert_eq!(Some(7i64), mod_inverse(&8, &11));
+        assert_eq!(None, mod_inverse(&4i64, &6));
+        assert_eq!(Some(9u128), mod_inverse(&5, &11));
+        for m in 2..40i64 {
+            for a in 0..m {
+                assert_eq!(extended_gcd(a, m).0, gcd(&a, &m));
+                match mod_inverse(&a, &m) {
+                    Some(x) => assert_eq!(1, a * x % m),
+                    None => assert_ne!(1, gcd(&a, &m)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generalised_crt() {
+        assert_eq!(
+            Ok(Congruence::new(23, 105)),
+            crt(&[
+                Congruence::new(2, 3),
+                Congruence::new(3, 5),
+                Congruence::new(2, 7)
+            ])
+        );
+        // 4 and 6 share a factor of 2, but 2 and 4 agree on it
+        assert_eq!(
+            Ok(Congruence::new(10, 12)),
+            crt(&[Congruence::new(2, 4), Congruence::new(4, 6)])
+        );
+        assert_eq!(
+            Err(
+                "no solution: Congruence { residue: 2, modulus: 6 } contradicts \
+                 Congruence { residue: 1, modulus: 4 }"
+                    .to_string()
+            ),
+            crt(&[Congruence::new(1, 4), Congruence::new(2, 6)])
+        );
+        assert_eq!(Ok(Congruence::new(0, 1)), crt(&[]));
+        let zero = Congruence {
+            residue: 1i64,
+            modulus: 0,
+        };
+        assert!(crt(&[zero]).is_err());
+        assert!(crt(&[
+            Congruence::new(0, 4_000_000_007),
+            Congruence::new(1, 4_000_000_009)
+        ])
+        .unwrap_err()
+        .contains("overflows"));
+
+        // u128 runs out where arbitrary precision doesn't, and they agree until then
+        let primes = [
+            4_294_967_291u32,
+            4_294_967_279,
+            4_294_967_231,
+            4_294_967_197,
+            4_294_967_189,
+        ];
+        let as_u128 = |count: usize| {
+            primes[..count]
+                .iter()
+                .enumerate()
+                .map(|(i, p)| Congruence {
+                    residue: i as u128,
+                    modulus: *p as u128,
+                })
+                .collect::<Vec<_>>()
+        };
+        let as_big = |count: usize| {
+            as_u128(count)
+                .iter()
+                .map(|c| Congruence {
+                    residue: BigNat::from_u128(c.residue),
+                    modulus: BigNat::from_u128(c.modulus),
+                })
+                .collect::<Vec<_>>()
+        };
+        let small = try_crt(&as_u128(4)).unwrap().unwrap();
+        let big = try_crt(&as_big(4)).unwrap().unwrap();
+        assert_eq!(Some(small.residue), big.residue.to_u128());
+        assert_eq!(Some(small.modulus), big.modulus.to_u128());
+        assert_eq!(None, try_crt(&as_u128(5)));
+        let big = crt(&as_big(5)).unwrap();
+        for (i, p) in primes.iter().enumerate() {
+            let remainder = big.residue.div_rem(&BigNat::from_u128(*p as u128)).1;
+            assert_eq!(BigNat::from_u128(i as u128), remainder);
+        }
+        assert_eq!(None, big.modulus.to_u128());
+
+        // everything against brute force
+        for m1 in 1..13 {
+            for m2 in 1..13 {
+                for a1 in 0..m1 {
+                    for a2 in 0..m2 {
+                        let both = |x: &i64| x % m1 == a1 && x % m2 == a2;
+                        let merged = crt(&[Congruence::new(a1, m1), Congruence::new(a2, m2)]);
+                        match (0..m1 * m2).find(both) {
+                            Some(x) => {
+                                let merged = merged.unwrap();
+                                assert_eq!(x, merged.residue);
+                                assert_eq!(m1 * m2 / extended_gcd(m1, m2).0, merged.modulus);
+                            }
+                            None => assert!(merged.is_err()),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 #![feature(drain_filter, never_type)]
+#![cfg_attr(test, feature(test))]
 #[macro_use]
 extern crate nom;
 